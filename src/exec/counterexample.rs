@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Write;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use itertools::Itertools;
+use serde::Serialize;
+
+use crate::{positioned::SourcePos, Expression, Options};
+
+use super::State;
+
+/// Controls how a failed [`crate::verify`] run reports its result.
+///
+/// `Text` writes only the human-oriented log and the recorded schedule next to `log_path`;
+/// `Json` additionally emits a [`Counterexample`] document, mirroring how
+/// `rustc --error-format=json` lets tooling consume a compiler error without
+/// scraping human-oriented text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ResultFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+/// A single (thread, program counter) pair executed on the path leading to the violation.
+#[derive(Debug, Clone, Serialize)]
+pub struct TraceStep {
+    pub tid: u64,
+    pub pc: u64,
+}
+
+/// A `SymbolicRef` that was resolved to a concrete object while exploring the failing path.
+#[derive(Debug, Clone, Serialize)]
+pub struct AliasBinding {
+    pub symbolic_var: String,
+    pub reference: i64,
+}
+
+/// A lock handoff: `lock` became free and `woken` was enabled to contend for it, recorded
+/// separately from `trace` since being woken isn't the same as being scheduled — `woken` may
+/// not run the very next step.
+#[derive(Debug, Clone, Serialize)]
+pub struct WakeupEvent {
+    pub woken: u64,
+    pub lock: i64,
+    pub at_pc: u64,
+}
+
+/// Machine-readable counterexample for a failed verification.
+///
+/// Contains everything a human would otherwise reconstruct by hand from `log.txt`:
+/// the violated assertion, the known concrete locals at the point of failure, the
+/// executed path and the alias resolutions that path depended on. See [`Counterexample::model`]
+/// for why this falls short of an actual Z3-produced satisfying model.
+#[derive(Debug, Clone, Serialize)]
+pub struct Counterexample {
+    /// The assertion or exceptional clause that was violated, pretty-printed.
+    pub violation: String,
+    /// Source position of the violation, in `Debug` form since `SourcePos` has no stable JSON shape.
+    pub violation_pos: String,
+    /// Known concrete locals at the point of violation, across every thread's top stack frame,
+    /// keyed by `"{tid}#{var}"`. This is NOT the satisfying model Z3 found for `path_condition` —
+    /// this tree has no Z3 solver integration to query for one, so any binding that's still
+    /// genuinely symbolic here (the normal case for the inputs that actually drove a failure) is
+    /// simply absent rather than resolved to the concrete value Z3 assigned it.
+    pub model: HashMap<String, String>,
+    /// The ordered sequence of statements executed to reach the violation.
+    pub trace: Vec<TraceStep>,
+    /// Resolved `SymbolicRef -> Reference` bindings observed along the trace.
+    pub aliases: Vec<AliasBinding>,
+    /// Lock handoffs observed along the path, in the order they happened.
+    pub wakeups: Vec<WakeupEvent>,
+}
+
+impl Counterexample {
+    pub fn from_state(state: &State, violation: impl Into<String>, violation_pos: SourcePos) -> Counterexample {
+        let trace = state
+            .path
+            .iter()
+            .map(|(tid, pc)| TraceStep { tid: *tid, pc: *pc })
+            .collect_vec();
+
+        let model = state
+            .threads
+            .iter()
+            .flat_map(|(tid, thread)| {
+                thread.stack.current_stackframe().params.iter().filter_map(move |(var, value)| match value.as_ref() {
+                    Expression::Lit { lit, .. } => Some((format!("{}#{}", tid, var), format!("{:?}", lit))),
+                    _ => None,
+                })
+            })
+            .collect();
+
+        let aliases = state
+            .alias_map
+            .iter()
+            .filter_map(|(var, entry)| match entry.aliases().first().map(Rc::as_ref) {
+                Some(Expression::Ref { ref_, .. }) => Some(AliasBinding {
+                    symbolic_var: var.to_string(),
+                    reference: *ref_,
+                }),
+                _ => None,
+            })
+            .collect();
+
+        let wakeups = state
+            .wakeups
+            .iter()
+            .map(|&(tid, lock, at_pc)| WakeupEvent { woken: tid, lock, at_pc })
+            .collect();
+
+        Counterexample {
+            violation: violation.into(),
+            violation_pos: format!("{:?}", violation_pos),
+            model,
+            trace,
+            aliases,
+            wakeups,
+        }
+    }
+}
+
+/// Records the failing schedule (see [`record_schedule`]) and, when `options.result_format`
+/// requests it, emits the counterexample as JSON next to `options.log_path`. Under the default
+/// `ResultFormat::Text` only the schedule is written.
+pub(super) fn report_counterexample(state: &State, violation: impl Into<String>, violation_pos: SourcePos, options: &Options) {
+    record_schedule(state, options);
+
+    if options.result_format != ResultFormat::Json {
+        return;
+    }
+
+    let counterexample = Counterexample::from_state(state, violation, violation_pos);
+
+    let json = serde_json::to_string_pretty(&counterexample).expect("counterexample is always serializable");
+
+    let path = PathBuf::from(options.log_path).with_extension("counterexample.json");
+    match File::create(&path) {
+        Ok(mut file) => {
+            let _ = file.write_all(json.as_bytes());
+        }
+        Err(_) => println!("{}", json),
+    }
+}
+
+/// Serializes `state.path` — the ordered `(tid, pc)` schedule that produced this violation —
+/// next to `options.log_path`, independent of `options.result_format`, so it's always
+/// available to feed back into `options.replay` for a deterministic single-path re-execution.
+fn record_schedule(state: &State, options: &Options) {
+    let schedule = state.path.iter().map(|(tid, pc)| TraceStep { tid: *tid, pc: *pc }).collect_vec();
+
+    let json = serde_json::to_string_pretty(&schedule).expect("schedule is always serializable");
+
+    let path = PathBuf::from(options.log_path).with_extension("schedule.json");
+    match File::create(&path) {
+        Ok(mut file) => {
+            let _ = file.write_all(json.as_bytes());
+        }
+        Err(_) => println!("{}", json),
+    }
+}
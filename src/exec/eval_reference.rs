@@ -19,7 +19,7 @@ pub trait ExecRef {
         // The Symbolic reference requires a state split.
         match object.as_ref() {
             Expression::Ref { ref_, .. } => {
-                Self::exec_over_ref(state, ref_);
+                Self::exec_over_ref(state, ref_, en);
             },
             Expression::SymbolicRef { var, type_, .. } => {
                 if let None = state.alias_map.get(var) {
@@ -59,7 +59,7 @@ pub trait ExecRef {
 
             return match expr {
                 Expression::Ref { ref_, .. } => {
-                    Self::exec_over_ref(state, &ref_);
+                    Self::exec_over_ref(state, &ref_, en);
                 }
                 _ => unreachable!("Expected ref")
             }
@@ -70,7 +70,7 @@ pub trait ExecRef {
         Self::exec(state, var, en);
     }
 
-    fn exec_over_ref(state: &mut State, ref_: &Reference);
+    fn exec_over_ref(state: &mut State, ref_: &Reference, en: &mut impl Engine);
 }
 
 pub(super) fn get_reference() {
@@ -4,13 +4,21 @@ use std::{cell::RefCell, collections::HashMap, ops::DerefMut, rc::Rc};
 use slog::{o, Logger};
 
 use crate::{
-    cfg::CFGStatement, exec::{action, locks::{check_deadlock, update_joins, update_next_locks}, mpor::validate_quasi_monotonicity, ActionResult, ThreadState}, positioned::SourcePos, statistics::Statistics, symbol_table::SymbolTable, Options
+    cfg::CFGStatement, exec::{action, locks::{check_deadlock, update_joins, update_next_locks}, mpor::{dpor_backtrack_set, successor_sleep_set, validate_quasi_monotonicity}, ActionResult, ThreadState}, positioned::SourcePos, statistics::Statistics, symbol_table::SymbolTable, Options
 };
 
 use execution_tree::ExecutionTree;
 
-use super::{IdCounter, State, SymResult};
+use super::{
+    counterexample::report_counterexample,
+    por::ample_set,
+    state_merge::{join_points_of, merge_states},
+    IdCounter, State, SymResult,
+};
+
+use const_prop::thread_constant_branches;
 
+pub mod a_star;
 pub mod depth_first_search;
 pub mod min_dist_to_uncovered;
 pub mod random_path;
@@ -19,7 +27,12 @@ pub mod round_robin;
 type Cost = u64;
 pub type ProgramCounter = u64;
 
+mod const_prop;
+mod dominators;
+mod dot_export;
 mod execution_tree;
+mod md2u_recursive;
+mod scc;
 mod utils;
 
 /// Given a set of states, which are all assumed to be at the same program point,
@@ -45,6 +58,14 @@ fn execute_instruction_for_all_states(
 
     let mut resulting_states: HashMap<u64, Vec<State>> = HashMap::new();
 
+    // Conditional-constant propagation: branches whose guard is statically decided by a
+    // constant assigned along the unique chain leading into them can be threaded straight to
+    // their known successor, skipping both the fork into the other successor and the
+    // eval_assertion/solver call that would otherwise have pruned it. Recomputed per batch
+    // rather than once for the whole search, since nothing here persists that analysis across
+    // calls.
+    let threaded_branches = thread_constant_branches(program, flows);
+
     // debug!(
     //     root_logger,
     //     "number of states: {:?}",
@@ -66,32 +87,83 @@ fn execute_instruction_for_all_states(
             options,
         };
         //MPOR
-        if let Some((_, pc)) = state.path.last() {
-            if  !validate_quasi_monotonicity(
-                    state, 
-                    program[pc].clone(), 
-                    engine
-            ) {
-                continue;
+        if !options.dpor {
+            if let Some((_, pc)) = state.path.last() {
+                if  !validate_quasi_monotonicity(
+                        state,
+                        program[pc].clone(),
+                        engine
+                ) {
+                    continue;
+                }
             }
-        }        
+        }
 
         update_next_locks(state, &program[&state.threads[&state.active_thread].pc], engine);
         update_joins(state, program);
-        check_deadlock(state);
+        if let Some(deadlock) = check_deadlock(state) {
+            let violation = format!(
+                "deadlock: threads {:?} waiting on locks {:?}",
+                deadlock.threads, deadlock.locks
+            );
+            report_counterexample(state, violation, SourcePos::UnknownPosition, options);
+            return Err(SourcePos::UnknownPosition);
+        }
 
 
+        // Partial-order reduction: rather than branching into every enabled thread, only
+        // expand a reduced set of transitions known to represent every other enabled one.
         let mut transition_states = vec![];
-        for thread in state.threads.values() {
-            if thread.state != ThreadState::Enabled {continue;}
+        let forced_tid = options.replay.as_ref().and_then(|schedule| schedule.get(state.path.len())).map(|&(tid, _)| tid);
+        if let Some(tid) = forced_tid {
+            // Replay mode: force the single thread dictated by the recorded schedule instead
+            // of fanning out into every enabled thread, for a fast deterministic re-execution.
             let mut new_state = state.clone();
-            new_state.active_thread = thread.tid;     
+            new_state.active_thread = tid;
             transition_states.push(new_state);
+        } else if options.dpor {
+            // DPOR: backtrack into exactly the threads dependent with the active one, and
+            // carry sleep sets forward so siblings already explored from this node aren't
+            // re-explored from a later one.
+            let parent_sleep = state.sleep.clone();
+            let mut processed_siblings = std::collections::HashSet::new();
+            for tid in dpor_backtrack_set(state, program, engine) {
+                let sleep = successor_sleep_set(state, program, engine, &parent_sleep, tid)
+                    .into_iter()
+                    .chain(processed_siblings.iter().copied())
+                    .collect();
+                let mut new_state = state.clone();
+                new_state.active_thread = tid;
+                new_state.sleep = sleep;
+                transition_states.push(new_state);
+                processed_siblings.insert(tid);
+            }
+        } else {
+            for tid in ample_set(state, program, engine) {
+                let mut new_state = state.clone();
+                new_state.active_thread = tid;
+                transition_states.push(new_state);
+            }
         }
 
         // if transition_states.len() == 0 {
         //     println!("{:?}", state.threads.values().map(|t| (t.tid, t.state.clone(), program[&t.pc].clone())).collect_vec());
         // }
+
+        // Preemption bound: a switch away from a thread that's itself still enabled is a
+        // preemption (as opposed to a "free" switch forced by the previous thread blocking or
+        // finishing); prune any successor that would exceed the budget `options.c`, so an
+        // iterative-deepening driver widening `c` across repeated runs explores shallow
+        // interleavings first.
+        let from_tid = state.active_thread;
+        let from_still_enabled = state.threads[&from_tid].state == ThreadState::Enabled;
+        transition_states.retain_mut(|new_state| {
+            if new_state.active_thread != from_tid && from_still_enabled {
+                new_state.preemptions += 1;
+            }
+            new_state.preemptions <= options.c
+        });
+
         scheduled_states.extend(transition_states);
     }
 
@@ -146,17 +218,38 @@ fn execute_instruction_for_all_states(
                 }
             }
             ActionResult::Continue => {
-                if let Some(neighbours) = flows.get(&state.threads.get_mut(&state.active_thread).unwrap().pc) {
+                let branch_pc = state.threads.get_mut(&state.active_thread).unwrap().pc;
+                if let Some(neighbours) = flows.get(&branch_pc) {
                     //dbg!(&neighbours);
                     statistics.measure_branches((neighbours.len() - 1) as u32);
 
-                    let mut neighbours = neighbours.iter();
+                    // In replay mode, don't fan out into every branch target: force the one
+                    // dictated by the recorded schedule at this step, so a found bug replays
+                    // as a single deterministic path instead of re-searching the whole space.
+                    let forced_pc = threaded_branches
+                        .get(&branch_pc)
+                        .copied()
+                        // A statically threaded branch always takes the same target, so it
+                        // takes priority over a recorded replay schedule if the two disagree.
+                        .or_else(|| {
+                            options
+                                .replay
+                                .as_ref()
+                                .and_then(|schedule| schedule.get(state.path.len()))
+                                .map(|&(_, pc)| pc)
+                        });
+
+                    let branch_targets: Vec<u64> = match forced_pc {
+                        Some(forced) if neighbours.contains(&forced) => vec![forced],
+                        _ => neighbours.clone(),
+                    };
+                    let mut neighbours = branch_targets.iter();
                     let first_neighbour = neighbours.next().unwrap();
                     state.threads.get_mut(&state.active_thread).unwrap().pc = *first_neighbour;
                     state.path_length += 1;
 
                     let new_path_ids = (1..).map(|_| path_counter.borrow_mut().next_id());
-                    
+
                     for (neighbour_pc, path_id) in neighbours.zip(new_path_ids) {
                         let mut new_state = state.clone();
                         new_state.path_id = path_id;
@@ -182,9 +275,11 @@ fn execute_instruction_for_all_states(
                 }
             }
             ActionResult::InvalidAssertion(info) => {
+                report_counterexample(&state, "assertion violated", info, options);
                 return Err(info);
             },
             ActionResult::InvalidFork(info) => {
+                report_counterexample(&state, "invalid fork", info, options);
                 return Err(info);
             }
             ActionResult::InfeasiblePath => {
@@ -206,6 +301,14 @@ fn execute_instruction_for_all_states(
     //     dbg!(&program[&current_pc.unwrap()]);
     // }
 
+    // Recombine states that reconverged at the same program point, countering the
+    // state-count explosion from SymbolicRef alias splitting.
+    let join_points = join_points_of(flows);
+    for states in resulting_states.values_mut() {
+        let states_at_pc = std::mem::take(states);
+        *states = merge_states(states_at_pc, options.merge_policy, &join_points);
+    }
+
     // Finished
     Ok(resulting_states)
 }
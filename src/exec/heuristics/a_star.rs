@@ -0,0 +1,188 @@
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::{BinaryHeap, HashMap},
+};
+
+use super::{
+    md2u_recursive::{min_distance_to_uncovered_method, Cache, Distance, DistanceType},
+    ProgramCounter,
+};
+use crate::{
+    cfg::{CFGStatement, MethodIdentifier},
+    exec::State,
+    symbol_table::SymbolTable,
+};
+
+/// A distance large enough to never be preferred over a state with a genuine
+/// `DistanceType::ToFirstUncovered` estimate. Used when a pc has no cached distance yet,
+/// or only a `ToEndOfMethod` bound, neither of which is a real uncovered target.
+const UNREACHABLE_BOUND: u64 = u64::MAX / 2;
+
+/// A frontier entry ordered by `f = g + h`: `g` is the number of statements already
+/// executed along this state's path, `h` is the admissible distance-to-uncovered estimate.
+/// `at_branch` breaks ties in favour of a state sitting at a pc that's the nearest dominating
+/// branch for some uncovered target — the decision point a directed search should take first,
+/// among states it's otherwise equally eager to explore.
+struct Frontier {
+    f: u64,
+    g: u64,
+    at_branch: bool,
+    state: State,
+}
+
+impl PartialEq for Frontier {
+    fn eq(&self, other: &Self) -> bool {
+        self.f == other.f && self.at_branch == other.at_branch
+    }
+}
+
+impl Eq for Frontier {}
+
+impl PartialOrd for Frontier {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Frontier {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.f.cmp(&other.f).then_with(|| other.at_branch.cmp(&self.at_branch))
+    }
+}
+
+/// Directed search strategy that orders the frontier of symbolic states by A*, using
+/// `min_distance_to_uncovered_method`'s `pc_to_distance` map as an admissible heuristic.
+/// Every statement costs at least 1 and the map reports the true shortest statement-count
+/// to the nearest uncovered target, so `f = g + h` never overestimates and the search
+/// reaches uncovered code with the fewest explored statements. States tied on `f` are then
+/// ordered by [`Frontier::at_branch`], steering the search towards the nearest dominating
+/// branch instead of leaving the tie broken arbitrarily.
+pub struct AStarSearch {
+    frontier: BinaryHeap<Reverse<Frontier>>,
+}
+
+impl AStarSearch {
+    pub fn new() -> AStarSearch {
+        AStarSearch {
+            frontier: BinaryHeap::new(),
+        }
+    }
+
+    /// Pushes `state`, which has executed `g` statements so far, looking up its heuristic
+    /// value for its current pc in `pc_to_distance`. `nearest_branches` (the value side of
+    /// [`super::md2u_recursive::nearest_dominating_branches`]'s map) breaks ties among
+    /// equal-`f` states in favour of one sitting at a branch some uncovered target needs taken.
+    pub fn push(
+        &mut self,
+        state: State,
+        g: u64,
+        pc_to_distance: &HashMap<ProgramCounter, Distance>,
+        nearest_branches: &HashMap<ProgramCounter, ProgramCounter>,
+    ) {
+        let pc = state.threads[&state.active_thread].pc;
+        let h = heuristic(pc, pc_to_distance);
+        let at_branch = nearest_branches.values().any(|&branch| branch == pc);
+        self.frontier.push(Reverse(Frontier { f: g + h, g, at_branch, state }));
+    }
+
+    /// Pops the lowest-`f` state, along with the number of statements executed to reach it.
+    pub fn pop(&mut self) -> Option<(State, u64)> {
+        self.frontier.pop().map(|Reverse(entry)| (entry.state, entry.g))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frontier.is_empty()
+    }
+}
+
+impl Default for AStarSearch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn heuristic(pc: ProgramCounter, pc_to_distance: &HashMap<ProgramCounter, Distance>) -> u64 {
+    match pc_to_distance.get(&pc) {
+        Some(distance) if distance.distance_type == DistanceType::ToFirstUncovered => distance.value,
+        _ => UNREACHABLE_BOUND,
+    }
+}
+
+/// A pluggable policy for ordering the frontier of execution states still to be explored,
+/// so different traversal orders can be swapped in without the scheduler caring which one
+/// drives it.
+pub(in crate::exec::heuristics) trait SearchStrategy<'a> {
+    /// Pushes `state`, which has executed `g` statements on its current path and belongs to
+    /// `method`, onto the frontier.
+    fn push(&mut self, state: State, g: u64, method: MethodIdentifier<'a>);
+
+    /// Pops whichever state the strategy considers most promising to explore next, along
+    /// with the number of statements executed to reach it.
+    fn pop(&mut self) -> Option<(State, u64)>;
+
+    fn is_empty(&self) -> bool;
+
+    /// Notifies the strategy that `newly_covered` pcs in `method` were just covered, so any
+    /// cached heuristic estimates depending on them can be refreshed. A no-op by default,
+    /// since most strategies don't maintain coverage-dependent state.
+    fn notify_new_coverage(&mut self, newly_covered: &[ProgramCounter], method: MethodIdentifier<'a>) {
+        let _ = (newly_covered, method);
+    }
+}
+
+/// A [`SearchStrategy`] that drives an [`AStarSearch`] frontier using
+/// `min_distance_to_uncovered_method`'s pc-to-distance map as its admissible heuristic. The
+/// map is looked up through `cache` on every push rather than kept in a field of its own,
+/// since `Cache` entries already carry a coverage fingerprint and recompute themselves
+/// exactly when they've gone stale.
+pub(in crate::exec::heuristics) struct DirectedSearch<'a> {
+    frontier: AStarSearch,
+    program: &'a HashMap<ProgramCounter, CFGStatement>,
+    flow: &'a HashMap<ProgramCounter, Vec<ProgramCounter>>,
+    symbol_table: &'a SymbolTable,
+    coverage: HashMap<ProgramCounter, usize>,
+    cache: Cache<'a>,
+}
+
+impl<'a> DirectedSearch<'a> {
+    pub(in crate::exec::heuristics) fn new(
+        program: &'a HashMap<ProgramCounter, CFGStatement>,
+        flow: &'a HashMap<ProgramCounter, Vec<ProgramCounter>>,
+        symbol_table: &'a SymbolTable,
+        coverage: HashMap<ProgramCounter, usize>,
+    ) -> DirectedSearch<'a> {
+        DirectedSearch {
+            frontier: AStarSearch::new(),
+            program,
+            flow,
+            symbol_table,
+            coverage,
+            cache: Cache::new(),
+        }
+    }
+}
+
+impl<'a> SearchStrategy<'a> for DirectedSearch<'a> {
+    fn push(&mut self, state: State, g: u64, method: MethodIdentifier<'a>) {
+        let (_, pc_to_distance, nearest_branches) =
+            min_distance_to_uncovered_method(method, &self.coverage, self.program, self.flow, self.symbol_table, &mut self.cache);
+        self.frontier.push(state, g, &pc_to_distance, &nearest_branches);
+    }
+
+    fn pop(&mut self) -> Option<(State, u64)> {
+        self.frontier.pop()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.frontier.is_empty()
+    }
+
+    /// Marks `newly_covered` as covered. No explicit cache eviction is needed: the next push
+    /// recomputes `method`'s fingerprint from the updated coverage, which is enough for
+    /// `Cache` to detect staleness on its own, for `method` and for any cached caller that
+    /// transitively depended on it.
+    fn notify_new_coverage(&mut self, newly_covered: &[ProgramCounter], method: MethodIdentifier<'a>) {
+        let _ = method;
+        self.coverage.extend(newly_covered.iter().map(|&pc| (pc, 1usize)));
+    }
+}
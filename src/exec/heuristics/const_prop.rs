@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+
+use crate::{cfg::CFGStatement, exec::solver_cache::fingerprint, BinOp, Expression, Identifier, Lhs, Rhs, Statement};
+
+use super::ProgramCounter;
+
+/// How many predecessor edges [`thread_constant_branches`] follows backwards from a branch
+/// before giving up on it. Bounds the analysis to a cost linear in a method's size instead of
+/// blowing up walking deep straight-line chains.
+const MAX_DEPTH: usize = 64;
+
+/// Branch pcs whose guard is statically decided by every path reaching them through the
+/// recorded single-predecessor chain, mapped to the successor they always take.
+/// `execute_instruction_for_all_states` can rewrite a threaded branch directly to its target
+/// and skip both the fork into the other successor and the `eval_assertion`/solver call that
+/// would otherwise have pruned it.
+pub(in crate::exec::heuristics) type ThreadedEdges = HashMap<ProgramCounter, ProgramCounter>;
+
+/// Finds constant-propagation threading opportunities across every branch (`Statement::Ite`)
+/// in `program`, borrowing the idea from MIR jump threading: from each branch, walk backwards
+/// through single-successor, single-predecessor CFG edges only (so nothing on a sibling path
+/// could have clobbered a tracked place), folding each `place = literal` assignment seen along
+/// the way into a `place -> literal` map, dropping any place that's assigned something else.
+/// A branch whose guard is exactly `place == literal` for some place still carrying that same
+/// literal in the map collapses to a `Goto` into the matching successor (`successors[0]` for
+/// equal, `successors[1]` for a tracked-but-different literal).
+///
+/// Only threads through a predecessor with no other successors of its own; a predecessor
+/// shared by other paths would need its block duplicated to thread safely, which this bounded
+/// pass doesn't attempt.
+pub(in crate::exec::heuristics) fn thread_constant_branches(
+    program: &HashMap<ProgramCounter, CFGStatement>,
+    flow: &HashMap<ProgramCounter, Vec<ProgramCounter>>,
+) -> ThreadedEdges {
+    let predecessors = reverse_edges(flow);
+
+    let mut threaded = ThreadedEdges::new();
+    for (&pc, successors) in flow {
+        if successors.len() != 2 {
+            continue;
+        }
+        let Some(CFGStatement::Statement(Statement::Ite { guard, .. })) = program.get(&pc) else {
+            continue;
+        };
+
+        let Some((place, literal)) = equality_guard(guard) else {
+            continue;
+        };
+
+        let Some(tracked) = walk_back_constants(pc, &predecessors, flow, program, &place) else {
+            continue;
+        };
+
+        let target = if fingerprint(&tracked) == fingerprint(&literal) {
+            successors[0]
+        } else {
+            successors[1]
+        };
+        threaded.insert(pc, target);
+    }
+
+    threaded
+}
+
+/// Splits a guard of the shape `place == literal` (in either operand order) into the tracked
+/// place and the literal it's compared against, the only guard shape this pass threads.
+fn equality_guard(guard: &Expression) -> Option<(Identifier, std::rc::Rc<Expression>)> {
+    let Expression::BinOp { bin_op: BinOp::Equal, lhs, rhs, .. } = guard else {
+        return None;
+    };
+
+    match (lhs.as_ref(), rhs.as_ref()) {
+        (Expression::Var { var, .. }, Expression::Lit { .. }) => Some((var.clone(), rhs.clone())),
+        (Expression::Lit { .. }, Expression::Var { var, .. }) => Some((var.clone(), lhs.clone())),
+        _ => None,
+    }
+}
+
+/// Walks backwards from `branch_pc` through single-predecessor, single-successor edges,
+/// looking for the literal last assigned to `place`. Stops (with no result) as soon as it
+/// passes [`MAX_DEPTH`] steps, reaches a predecessor with more than one successor (meaning an
+/// intervening branch could have taken a path that skipped the assignment), or reaches a
+/// predecessor shared by another branch's successor (duplicating would be required to thread
+/// through it safely).
+fn walk_back_constants(
+    branch_pc: ProgramCounter,
+    predecessors: &HashMap<ProgramCounter, Vec<ProgramCounter>>,
+    flow: &HashMap<ProgramCounter, Vec<ProgramCounter>>,
+    program: &HashMap<ProgramCounter, CFGStatement>,
+    place: &Identifier,
+) -> Option<std::rc::Rc<Expression>> {
+    let mut current = branch_pc;
+    for _ in 0..MAX_DEPTH {
+        let preds = predecessors.get(&current)?;
+        let [predecessor] = preds.as_slice() else {
+            return None;
+        };
+        if flow.get(predecessor).map_or(0, Vec::len) != 1 {
+            return None;
+        }
+
+        if let Some(CFGStatement::Statement(Statement::Assign { lhs, rhs, .. })) = program.get(predecessor) {
+            match lhs {
+                Lhs::LhsVar { var, .. } if var == place => {
+                    return match rhs {
+                        Rhs::RhsExpression { value, .. } if matches!(value.as_ref(), Expression::Lit { .. }) => Some(value.clone()),
+                        _ => None,
+                    };
+                }
+                Lhs::LhsVar { var, .. } if var != place => {}
+                _ => {}
+            }
+        }
+
+        current = *predecessor;
+    }
+    None
+}
+
+fn reverse_edges(flow: &HashMap<ProgramCounter, Vec<ProgramCounter>>) -> HashMap<ProgramCounter, Vec<ProgramCounter>> {
+    let mut reverse: HashMap<ProgramCounter, Vec<ProgramCounter>> = HashMap::new();
+    for (&from, tos) in flow {
+        for &to in tos {
+            reverse.entry(to).or_default().push(from);
+        }
+    }
+    reverse
+}
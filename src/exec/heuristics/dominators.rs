@@ -0,0 +1,228 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::{
+    cfg::{CFGStatement, MethodIdentifier},
+    exec::find_entry_for_static_invocation,
+    symbol_table::SymbolTable,
+};
+
+use super::ProgramCounter;
+
+/// The immediate-dominator relation over a method's CFG, computed from a single entry (or,
+/// for [`post_dominators`], a single virtual exit joining every `FunctionExit`). A pc absent
+/// from the tree is unreachable from that entry and must be excluded from any computation
+/// built on top of it.
+#[derive(Debug, Clone)]
+pub(in crate::exec::heuristics) struct DominatorTree {
+    entry: ProgramCounter,
+    idom: HashMap<ProgramCounter, ProgramCounter>,
+}
+
+impl DominatorTree {
+    /// Whether `pc` is reachable from the tree's entry at all.
+    pub(in crate::exec::heuristics) fn is_reachable(&self, pc: ProgramCounter) -> bool {
+        pc == self.entry || self.idom.contains_key(&pc)
+    }
+
+    /// Whether `a` dominates `b`, i.e. every path from entry to `b` passes through `a`.
+    /// Unreachable nodes are dominated by nothing.
+    pub(in crate::exec::heuristics) fn dominates(&self, a: ProgramCounter, b: ProgramCounter) -> bool {
+        if !self.is_reachable(b) {
+            return false;
+        }
+        self.ancestors(b).any(|ancestor| ancestor == a)
+    }
+
+    /// Walks from `pc` up to the entry along the idom chain, yielding `pc` itself first and
+    /// the entry last. Empty if `pc` is unreachable.
+    pub(in crate::exec::heuristics) fn ancestors(&self, pc: ProgramCounter) -> impl Iterator<Item = ProgramCounter> + '_ {
+        let mut next = self.is_reachable(pc).then_some(pc);
+        std::iter::from_fn(move || {
+            let current = next?;
+            next = if current == self.entry { None } else { Some(self.idom[&current]) };
+            Some(current)
+        })
+    }
+
+    /// The nearest strict ancestor of `target` (i.e. excluding `target` itself) that branches
+    /// according to `flow`, i.e. the closest decision point that must be taken to reach
+    /// `target`. `None` if `target` is unreachable or no ancestor branches (it is always
+    /// reached once the entry is).
+    pub(in crate::exec::heuristics) fn nearest_dominating_branch(
+        &self,
+        target: ProgramCounter,
+        flow: &HashMap<ProgramCounter, Vec<ProgramCounter>>,
+    ) -> Option<ProgramCounter> {
+        self.ancestors(target)
+            .skip(1)
+            .find(|&ancestor| flow.get(&ancestor).is_some_and(|successors| successors.len() > 1))
+    }
+}
+
+/// Computes the (forward) dominator tree of `method`'s CFG, rooted at its entry pc.
+///
+/// Implements the iterative Cooper-Harvey-Kennedy algorithm: number every node reachable
+/// from the entry in reverse postorder, set `idom[entry] = entry`, then repeatedly sweep the
+/// remaining nodes in RPO, recomputing each node's `idom` as the common ancestor (by
+/// `intersect`) of all of its already-processed predecessors, until nothing changes. Runs in
+/// a handful of passes in practice since RPO order makes most nodes settle in pass one.
+pub(in crate::exec::heuristics) fn dominators<'a>(
+    method: MethodIdentifier<'a>,
+    program: &HashMap<ProgramCounter, CFGStatement>,
+    flow: &HashMap<ProgramCounter, Vec<ProgramCounter>>,
+    st: &SymbolTable,
+) -> DominatorTree {
+    let entry_pc = find_entry_for_static_invocation(
+        method.decl_name,
+        method.method_name,
+        method.arg_list.iter().cloned(),
+        program,
+        st,
+    );
+
+    build_dominator_tree(entry_pc, flow)
+}
+
+/// Computes the post-dominator tree of `method`'s CFG: the dominator tree of the reversed
+/// CFG, rooted at a virtual node joining every `FunctionExit` reachable from the entry.
+pub(in crate::exec::heuristics) fn post_dominators<'a>(
+    method: MethodIdentifier<'a>,
+    program: &HashMap<ProgramCounter, CFGStatement>,
+    flow: &HashMap<ProgramCounter, Vec<ProgramCounter>>,
+    st: &SymbolTable,
+) -> DominatorTree {
+    let entry_pc = find_entry_for_static_invocation(
+        method.decl_name,
+        method.method_name,
+        method.arg_list.iter().cloned(),
+        program,
+        st,
+    );
+
+    let forward_nodes = reachable_from(entry_pc, flow);
+    let reverse_flow = reverse_edges(flow);
+
+    // A single virtual exit joins every FunctionExit, so the CHK algorithm still has one
+    // well-defined root even when a method has several return statements.
+    const VIRTUAL_EXIT: ProgramCounter = ProgramCounter::MAX;
+    let exits: Vec<ProgramCounter> = forward_nodes
+        .iter()
+        .copied()
+        .filter(|pc| matches!(&program[pc], CFGStatement::FunctionExit { .. }))
+        .collect();
+
+    let mut reverse_flow_from_virtual_exit = reverse_flow;
+    reverse_flow_from_virtual_exit.insert(VIRTUAL_EXIT, exits);
+
+    build_dominator_tree(VIRTUAL_EXIT, &reverse_flow_from_virtual_exit)
+}
+
+fn build_dominator_tree(entry: ProgramCounter, flow: &HashMap<ProgramCounter, Vec<ProgramCounter>>) -> DominatorTree {
+    let rpo = reverse_postorder(entry, flow);
+    let postorder_number: HashMap<ProgramCounter, usize> =
+        rpo.iter().enumerate().map(|(i, &pc)| (pc, rpo.len() - 1 - i)).collect();
+    let reverse_flow = reverse_edges(flow);
+
+    let mut idom: HashMap<ProgramCounter, ProgramCounter> = HashMap::new();
+    idom.insert(entry, entry);
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+
+        for &node in rpo.iter().filter(|&&pc| pc != entry) {
+            let mut processed_predecessors = reverse_flow
+                .get(&node)
+                .into_iter()
+                .flatten()
+                .filter(|predecessor| idom.contains_key(predecessor));
+
+            let Some(&first) = processed_predecessors.next() else {
+                continue;
+            };
+
+            let new_idom = processed_predecessors.fold(first, |new_idom, &predecessor| {
+                intersect(predecessor, new_idom, &idom, &postorder_number)
+            });
+
+            if idom.get(&node) != Some(&new_idom) {
+                idom.insert(node, new_idom);
+                changed = true;
+            }
+        }
+    }
+
+    // The entry's own idom is only a sentinel for `intersect`'s finger-walk; it is not a
+    // real predecessor relationship, so ancestors()/dominates() special-case it instead.
+    idom.remove(&entry);
+
+    DominatorTree { entry, idom }
+}
+
+/// Walks the two idom finger pointers `a` and `b` upward, always advancing whichever has the
+/// smaller postorder number, until they meet at their common dominator.
+fn intersect(
+    mut a: ProgramCounter,
+    mut b: ProgramCounter,
+    idom: &HashMap<ProgramCounter, ProgramCounter>,
+    postorder_number: &HashMap<ProgramCounter, usize>,
+) -> ProgramCounter {
+    while a != b {
+        while postorder_number[&a] < postorder_number[&b] {
+            a = idom[&a];
+        }
+        while postorder_number[&b] < postorder_number[&a] {
+            b = idom[&b];
+        }
+    }
+    a
+}
+
+fn reachable_from(entry: ProgramCounter, flow: &HashMap<ProgramCounter, Vec<ProgramCounter>>) -> HashSet<ProgramCounter> {
+    let mut visited = HashSet::new();
+    let mut worklist = VecDeque::from([entry]);
+    while let Some(pc) = worklist.pop_front() {
+        if visited.insert(pc) {
+            worklist.extend(flow.get(&pc).into_iter().flatten().copied());
+        }
+    }
+    visited
+}
+
+/// Reverse-postorder numbering of every node reachable from `entry`: a DFS postorder,
+/// reversed, so that `entry` comes first and every node appears before all of its
+/// (non-back-edge) successors.
+fn reverse_postorder(entry: ProgramCounter, flow: &HashMap<ProgramCounter, Vec<ProgramCounter>>) -> Vec<ProgramCounter> {
+    let mut visited = HashSet::new();
+    let mut postorder = Vec::new();
+    let mut stack = vec![(entry, false)];
+
+    while let Some((pc, expanded)) = stack.pop() {
+        if expanded {
+            postorder.push(pc);
+            continue;
+        }
+        if !visited.insert(pc) {
+            continue;
+        }
+        stack.push((pc, true));
+        for &successor in flow.get(&pc).into_iter().flatten() {
+            if !visited.contains(&successor) {
+                stack.push((successor, false));
+            }
+        }
+    }
+
+    postorder.reverse();
+    postorder
+}
+
+fn reverse_edges(flow: &HashMap<ProgramCounter, Vec<ProgramCounter>>) -> HashMap<ProgramCounter, Vec<ProgramCounter>> {
+    let mut reverse: HashMap<ProgramCounter, Vec<ProgramCounter>> = HashMap::new();
+    for (&from, tos) in flow {
+        for &to in tos {
+            reverse.entry(to).or_default().push(from);
+        }
+    }
+    reverse
+}
@@ -0,0 +1,137 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::{
+    cfg::{CFGStatement, MethodIdentifier},
+    exec::find_entry_for_static_invocation,
+    symbol_table::SymbolTable,
+};
+
+use super::{
+    md2u_recursive::{is_method_invocation, methods_called, Distance, DistanceType},
+    ProgramCounter,
+};
+
+/// Renders `method`'s interprocedural CFG as Graphviz DOT, the machine-readable sibling of
+/// `pretty_print_cfg_method`'s text output: one node per pc (labelled via `label_fn`, falling
+/// back to its bare pc when `label_fn` returns `None`), solid edges for `flow`, and dashed
+/// edges for method calls. Every callee discovered this way gets its own `subgraph cluster_*`
+/// so recursive calls (e.g. into `f_recursive`/`g_recursive`) render as visually distinct
+/// regions rather than bleeding into the caller's body.
+///
+/// Nodes are colored on a red-to-blue heat gradient by their `Distance.value` in
+/// `pc_to_distance` (red = close, blue = far), and drawn as a diamond for a genuine
+/// `ToFirstUncovered` target versus a box for a `ToEndOfMethod` bound, so a user can see at a
+/// glance which direction the distance heuristic is steering a directed search.
+pub(in crate::exec::heuristics) fn cfg_to_dot(
+    method: MethodIdentifier,
+    label_fn: &dyn Fn(ProgramCounter) -> Option<String>,
+    pc_to_distance: &HashMap<ProgramCounter, Distance>,
+    program: &HashMap<ProgramCounter, CFGStatement>,
+    flow: &HashMap<ProgramCounter, Vec<ProgramCounter>>,
+    st: &SymbolTable,
+) -> String {
+    let entry_pc = find_entry_for_static_invocation(method.decl_name, method.method_name, method.arg_list.iter().cloned(), program, st);
+
+    let (owner, call_edges) = discover_methods(method, entry_pc, program, flow, st);
+
+    let max_value = pc_to_distance.values().map(|distance| distance.value).max().unwrap_or(1).max(1);
+
+    let mut clusters: HashMap<&str, Vec<ProgramCounter>> = HashMap::new();
+    for (&pc, owning_method) in &owner {
+        clusters.entry(owning_method.method_name).or_default().push(pc);
+    }
+
+    let mut out = String::new();
+    out.push_str("digraph cfg {\n");
+    out.push_str("    node [style=filled];\n");
+
+    let mut cluster_id = 0;
+    for (method_name, mut pcs) in clusters {
+        pcs.sort_unstable();
+
+        out.push_str(&format!("    subgraph cluster_{cluster_id} {{\n"));
+        out.push_str(&format!("        label=\"{method_name}\";\n"));
+        cluster_id += 1;
+
+        for pc in pcs {
+            let label = label_fn(pc).unwrap_or_else(|| pc.to_string());
+            let distance = pc_to_distance.get(&pc);
+
+            let shape = match distance.map(|distance| distance.distance_type) {
+                Some(DistanceType::ToFirstUncovered) => "diamond",
+                Some(DistanceType::ToEndOfMethod) | None => "box",
+            };
+            let color = distance
+                .map(|distance| heat_color(distance.value, max_value))
+                .unwrap_or_else(|| "\"0.0,0.0,1.0\"".to_string());
+
+            out.push_str(&format!("        {pc} [label=\"{label}\", shape={shape}, fillcolor={color}];\n"));
+        }
+
+        out.push_str("    }\n");
+    }
+
+    for (&pc, successors) in flow {
+        if !owner.contains_key(&pc) {
+            continue;
+        }
+        for &successor in successors {
+            out.push_str(&format!("    {pc} -> {successor};\n"));
+        }
+    }
+
+    for (call_site, callee_entry) in &call_edges {
+        out.push_str(&format!("    {call_site} -> {callee_entry} [style=dashed];\n"));
+    }
+
+    out.push_str("}\n");
+    out
+}
+
+/// A Graphviz HSV color string interpolating from red (`value == 0`) to blue (`value ==
+/// max_value`), so the hottest (closest) nodes stand out and the coldest fade towards the
+/// unreached end of the gradient.
+fn heat_color(value: u64, max_value: u64) -> String {
+    let fraction = value as f64 / max_value as f64;
+    let hue = fraction.clamp(0.0, 1.0) * 0.666; // 0.0 = red, 0.666 = blue
+    format!("\"{hue:.3},0.85,0.95\"")
+}
+
+/// Discovers every pc reachable from `entry_pc`, following `flow` within a method and, at
+/// each call statement, descending into every resolved callee's own body too, recording which
+/// method owns each pc and the call-site-to-callee-entry edges crossed along the way. Mirrors
+/// `md2u_recursive::discover_interprocedural_cfg`'s traversal, but additionally tracks method
+/// ownership so callees can be rendered in their own cluster.
+fn discover_methods<'a>(
+    entry_method: MethodIdentifier<'a>,
+    entry_pc: ProgramCounter,
+    program: &'a HashMap<ProgramCounter, CFGStatement>,
+    flow: &HashMap<ProgramCounter, Vec<ProgramCounter>>,
+    st: &SymbolTable,
+) -> (HashMap<ProgramCounter, MethodIdentifier<'a>>, Vec<(ProgramCounter, ProgramCounter)>) {
+    let mut owner: HashMap<ProgramCounter, MethodIdentifier<'a>> = HashMap::new();
+    let mut call_edges = Vec::new();
+    let mut worklist = VecDeque::from([(entry_method, entry_pc)]);
+
+    while let Some((method, pc)) = worklist.pop_front() {
+        if owner.contains_key(&pc) {
+            continue;
+        }
+        owner.insert(pc, method);
+
+        if let Some(invocation) = is_method_invocation(&program[&pc]) {
+            for callee in methods_called(invocation) {
+                let callee_entry =
+                    find_entry_for_static_invocation(callee.decl_name, callee.method_name, callee.arg_list.iter().cloned(), program, st);
+                call_edges.push((pc, callee_entry));
+                worklist.push_back((callee, callee_entry));
+            }
+        }
+
+        for &successor in flow.get(&pc).into_iter().flatten() {
+            worklist.push_back((method, successor));
+        }
+    }
+
+    (owner, call_edges)
+}
@@ -1,529 +1,503 @@
-use std::collections::{HashMap, HashSet};
-
-use itertools::Itertools;
+use std::collections::{HashMap, HashSet, VecDeque};
 
 use crate::{
     cfg::{CFGStatement, MethodIdentifier},
     exec::find_entry_for_static_invocation,
     symbol_table::SymbolTable,
-    Invocation, Rhs, Statement,
+    Invocation,
 };
 
-use super::{Cost, ProgramCounter};
+use super::{
+    dominators::dominators,
+    scc::{tarjan_scc, StronglyConnectedComponents},
+    ProgramCounter,
+};
 
-/// A cache storing for a method the distance, will not contain distances for unexplored methods.
-/// A method always has the same cost, with a distinction made between a cost achieved by finding an uncovered statement,
-/// and otherwise a cost of calling the function in terms of the number of statements visited.
-type Cache<'a> = HashMap<MethodIdentifier<'a>, CumulativeCost>;
+/// A cache storing the resolved distance map for a method, keyed by the method it belongs
+/// to. Absence means the method hasn't been solved (yet).
+pub(in crate::exec::heuristics) type Cache<'a> = HashMap<MethodIdentifier<'a>, CacheEntry<'a>>;
+
+/// A method's cached distance solution, together with enough bookkeeping to tell whether
+/// it's still valid without re-running the fixpoint: the coverage fingerprint it was solved
+/// under, and the fingerprints of every callee it transitively depended on at that time. A
+/// query is a cache hit only if the method's own fingerprint and every dependency's current
+/// fingerprint still match what's stored here.
+#[derive(Debug, Clone)]
+pub(in crate::exec::heuristics) struct CacheEntry<'a> {
+    fingerprint: u64,
+    dependency_fingerprints: HashMap<MethodIdentifier<'a>, u64>,
+    entry_distance: Distance,
+    pc_to_distance: HashMap<ProgramCounter, Distance>,
+    nearest_branches: HashMap<ProgramCounter, ProgramCounter>,
+}
 
-/// Type of distance of a statement, can be either partially complete (to as far as it can see), which would be the exit of the method.
-/// Or it can be the distance to the first uncovered statement, if any found.
+/// Type of distance of a statement: either the distance to the first uncovered statement
+/// (a real search target), or only a bound on how far the end of the method is (no
+/// uncovered statement found on this path). Variant order doubles as the join order: a
+/// `ToFirstUncovered` distance always wins over a `ToEndOfMethod` one of any value.
 #[derive(Debug, Hash, Eq, PartialEq, Clone, PartialOrd, Ord, Copy)]
-enum DistanceType {
+pub(in crate::exec::heuristics) enum DistanceType {
     ToFirstUncovered,
     ToEndOfMethod,
 }
 
 #[derive(Debug, PartialOrd, Ord, PartialEq, Eq, Clone, Copy)]
-struct Distance {
-    distance_type: DistanceType,
-    value: u64,
+pub(in crate::exec::heuristics) struct Distance {
+    pub(in crate::exec::heuristics) distance_type: DistanceType,
+    pub(in crate::exec::heuristics) value: u64,
 }
 
 impl Distance {
-    fn plus(mut self, cost: Cost) -> Distance {
+    fn plus(mut self, cost: u64) -> Distance {
         self.value += cost;
         self
     }
 }
 
-/// calling a method will explore a certain number of statements before returning
-/// If an uncovered statement is encountered, it will have an exact cost
-/// Otherwise it returns the minimal cost of the method call in terms of the number of statements explored.
-#[derive(Debug, PartialEq, Eq, Clone)]
-enum CumulativeCost {
-    Cost(Distance),
-    /// Cycles back to program point (while loop), with additional cost.
-    Cycle(ProgramCounter, Cost),
-    Plus(Box<CumulativeCost>, Box<CumulativeCost>),
-    /// In case of recursion, we can not resolve it immediately and need to keep track of it.
-    UnexploredMethodCall(String),
-    /// Used to store cost when it cannot yet be determined which one is closer
-    Minimal(Box<CumulativeCost>, Box<CumulativeCost>),
+/// An effectively-infinite distance used to initialize the fixpoint lattice's `top`
+/// element; large enough that no real path will ever exceed it, but small enough that
+/// repeated `plus`-ing during relaxation can't overflow.
+const TOP: Distance = Distance {
+    distance_type: DistanceType::ToEndOfMethod,
+    value: u64::MAX / 4,
+};
+
+/// A 64-bit fingerprint of `method`'s relevant coverage subset: the set of pcs belonging to
+/// its own body (not its callees') that are currently covered. Two calls with the same
+/// covered subset always produce the same fingerprint, regardless of `HashMap`/`HashSet`
+/// iteration order, since the pcs are sorted before folding.
+fn fingerprint_of_method(
+    method: MethodIdentifier,
+    coverage: &HashMap<ProgramCounter, usize>,
+    program: &HashMap<ProgramCounter, CFGStatement>,
+    flow: &HashMap<ProgramCounter, Vec<ProgramCounter>>,
+    st: &SymbolTable,
+) -> u64 {
+    let entry_pc = find_entry_for_static_invocation(
+        method.decl_name,
+        method.method_name,
+        method.arg_list.iter().cloned(),
+        program,
+        st,
+    );
+
+    let covered = method_local_nodes(entry_pc, flow)
+        .into_iter()
+        .filter(|pc| coverage.contains_key(pc));
+
+    fingerprint(covered)
 }
 
-// Either<CostToUncoveredStatement, MinimalMethodCost>
+/// Folds a sequence of pcs (order-independent, since they're sorted first) into a stable
+/// 64-bit fingerprint by hashing each one with `SipHash` (via the standard library's
+/// `DefaultHasher`) and combining the halves with a running XOR/multiply mix.
+fn fingerprint(pcs: impl Iterator<Item = ProgramCounter>) -> u64 {
+    use std::hash::{Hash, Hasher};
 
-impl CumulativeCost {
-    fn increased_by_one(self) -> CumulativeCost {
-        self.plus(1)
-    }
+    let mut sorted: Vec<ProgramCounter> = pcs.collect();
+    sorted.sort_unstable();
 
-    fn plus(self, cost: Cost) -> CumulativeCost {
-        match self {
-            Self::Cost(d) => Self::Cost(d.plus(cost)),
-            Self::Cycle(pc, c) => Self::Cycle(pc, c + cost),
-            Self::Plus(c1, c2) => Self::Plus(c1, Box::new(c2.plus(cost))),
-            Self::UnexploredMethodCall(_) => Self::Plus(
-                Box::new(self),
-                Box::new(Self::Cost(Distance {
-                    value: cost,
-                    distance_type: DistanceType::ToEndOfMethod,
-                })),
-            ),
-            Self::Minimal(c1, c2) => {
-                Self::Minimal(Box::new(c1.plus(cost)), Box::new(c2.plus(cost)))
-            }
+    sorted.into_iter().fold(0u64, |acc, pc| {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        pc.hash(&mut hasher);
+        (acc ^ hasher.finish()).wrapping_mul(0x9E3779B97F4A7C15)
+    })
+}
+
+/// Every pc reachable from `entry_pc` by following `flow` alone, i.e. a single method's own
+/// body without descending into any callee (unlike `discover_interprocedural_cfg`, which
+/// does). Used to scope a fingerprint to exactly the pcs that belong to one method.
+fn method_local_nodes(entry_pc: ProgramCounter, flow: &HashMap<ProgramCounter, Vec<ProgramCounter>>) -> HashSet<ProgramCounter> {
+    let mut visited = HashSet::new();
+    let mut worklist = VecDeque::from([entry_pc]);
+    while let Some(pc) = worklist.pop_front() {
+        if visited.insert(pc) {
+            worklist.extend(flow.get(&pc).into_iter().flatten().copied());
         }
     }
+    visited
 }
 
-/// Computes the minimal distance to uncovered methods for all program counters in this method
-/// Recursively computes the minimal distance for any method calls referenced, in a depth-first-search way.
-/// Returns:
-///  - the distance of this method to the closest uncovered statement, or the end of the method.
-///  - a mapping for every program counter explored to its distance
-///  - a cache for methods explored may be reused.
-fn min_distance_to_uncovered_method<'a>(
-    method: MethodIdentifier<'a>,
-    coverage: &HashMap<ProgramCounter, usize>,
-    program: &'a HashMap<ProgramCounter, CFGStatement>,
-    flow: &HashMap<ProgramCounter, Vec<ProgramCounter>>,
-    st: &SymbolTable,
-    cache: &mut Cache<'a>
-) -> (Distance, HashMap<ProgramCounter, Distance>) {
-    // We reset visited when the search is started from scratch 
-    let mut visited = HashSet::new();
-    let (distance, pc_to_distance) =
-        min_distance_to_uncovered_method_helper1(method.clone(), coverage, program, flow, st, &mut visited, cache);
+/// Assigns a cost to exploring a single CFG statement, so the heuristic can be biased
+/// toward paths that are genuinely cheap to symbolically execute rather than merely short
+/// in statement count (heap allocations, array accesses and SMT-heavy guards are all more
+/// expensive than a plain assignment).
+pub(in crate::exec::heuristics) trait CostModel {
+    /// Base cost of executing `statement`, before any dynamic-dispatch penalty.
+    fn statement_cost(&self, statement: &CFGStatement) -> u64;
+
+    /// Additional cost charged for a method-call statement that resolves to
+    /// `resolved_target_count` possible dynamic-dispatch targets.
+    fn call_cost(&self, resolved_target_count: usize) -> u64 {
+        let _ = resolved_target_count;
+        0
+    }
 
-    (distance, pc_to_distance)
+    /// Additional cost charged when the distance pass relaxes across a back-edge that
+    /// re-enters a loop, on top of the edge's normal statement cost. Defaults to 0, so a
+    /// loop contributes no more than its acyclic shortest path unless a model opts in.
+    fn back_edge_penalty(&self) -> u64 {
+        0
+    }
 }
 
+/// The original behaviour: every statement and every method-call hop costs exactly 1.
+/// Kept as the default so existing callers and tests keep their current distances.
+pub(in crate::exec::heuristics) struct UniformCostModel;
 
-fn min_distance_to_uncovered_method_helper1<'a>(
-    method: MethodIdentifier<'a>,
-    coverage: &HashMap<ProgramCounter, usize>,
-    program: &'a HashMap<ProgramCounter, CFGStatement>,
-    flow: &HashMap<ProgramCounter, Vec<ProgramCounter>>,
-    st: &SymbolTable,
-    visited: &mut HashSet<ProgramCounter>,
-    cache: &mut Cache<'a>
-) -> (Distance, HashMap<ProgramCounter, Distance>) {
-    let (cost, pc_to_cost) =
-        min_distance_to_uncovered_method_helper(method.clone(), coverage, program, flow, st, visited, cache);
-
-    dbg!(&cost, &method);
-    let distance = if let CumulativeCost::Cost(distance) = cost {
-        distance
-    } else {
-        panic!("expected solved distance");
-    };
+impl CostModel for UniformCostModel {
+    fn statement_cost(&self, _statement: &CFGStatement) -> u64 {
+        1
+    }
+}
 
-    // at this point all cost should be concrete distances.
-    let pc_to_distance = pc_to_cost
-        .into_iter()
-        .map(|(key, value)| {
-            let distance = if let CumulativeCost::Cost(distance) = value {
-                distance
-            } else {
-                panic!("expected solved distance");
-            };
-            (key, distance)
-        })
-        .collect();
+/// Charges a branching penalty proportional to the number of dynamic-dispatch targets a
+/// call could resolve to, on top of the uniform per-statement cost, biasing the heuristic
+/// away from virtual calls with wide, expensive-to-explore dispatch sets.
+pub(in crate::exec::heuristics) struct DynamicDispatchPenaltyCostModel {
+    pub(in crate::exec::heuristics) penalty_per_target: u64,
+}
 
-    // Clean up cache of incomplete methods
-    let to_remove = cache.iter().filter_map(|(k, v)| if let CumulativeCost::Cost(d) = v {
-        if d.distance_type == DistanceType::ToFirstUncovered {
-            Some(k.clone())
-        } else {
-            None
-        }
-    } else {
-        Some(k.clone())
-    }).collect_vec();
+impl CostModel for DynamicDispatchPenaltyCostModel {
+    fn statement_cost(&self, _statement: &CFGStatement) -> u64 {
+        1
+    }
 
-    cache.retain(|k, _v| !to_remove.contains(&k));
+    fn call_cost(&self, resolved_target_count: usize) -> u64 {
+        self.penalty_per_target * resolved_target_count as u64
+    }
+}
 
-    (distance, pc_to_distance)
+/// Charges a fixed penalty, on top of the uniform per-statement cost, every time the
+/// distance pass relaxes across a loop back-edge, biasing the heuristic towards uncovered
+/// targets that require fewer loop iterations to reach.
+pub(in crate::exec::heuristics) struct LoopPenaltyCostModel {
+    pub(in crate::exec::heuristics) penalty_per_back_edge: u64,
 }
 
-/// Computes the minimal distance to uncovered methods for all program counters in this method
-/// Recursively computes the minimal distance for any method calls referenced.
-fn min_distance_to_uncovered_method_helper<'a>(
-    method: MethodIdentifier<'a>,
-    coverage: &HashMap<ProgramCounter, usize>,
-    program: &'a HashMap<ProgramCounter, CFGStatement>,
+impl CostModel for LoopPenaltyCostModel {
+    fn statement_cost(&self, _statement: &CFGStatement) -> u64 {
+        1
+    }
+
+    fn back_edge_penalty(&self) -> u64 {
+        self.penalty_per_back_edge
+    }
+}
+
+/// Invalidates exactly the entries affected by `newly_covered` instead of discarding the
+/// whole `Cache`/`pc_to_distance`. Marking a pc covered can only *increase* distances for
+/// pcs that could reach it, so a dirty-set propagation along the reverse of `flow` is
+/// enough: seed the worklist with the newly-covered pcs, walk predecessors, and drop their
+/// entries from `pc_to_distance`. Any cached method whose distance map contains a dirtied pc
+/// (in its own body or in a callee's body it calls into) has its entry dropped too, since that
+/// entry may have folded in the now-stale cost.
+pub(in crate::exec::heuristics) fn invalidate_for_new_coverage<'a>(
+    newly_covered: &[ProgramCounter],
+    _program: &'a HashMap<ProgramCounter, CFGStatement>,
     flow: &HashMap<ProgramCounter, Vec<ProgramCounter>>,
-    st: &SymbolTable,
-    visited: &mut HashSet<ProgramCounter>,
-    cache: &mut Cache<'a>
-) -> (
-    CumulativeCost,
-    HashMap<ProgramCounter, CumulativeCost>
+    pc_to_distance: &mut HashMap<ProgramCounter, Distance>,
+    cache: &mut Cache<'a>,
 ) {
-    let pc = find_entry_for_static_invocation(
-        method.decl_name,
-        method.method_name,
-        method.arg_list.iter().cloned(),
-        program,
-        st,
-    );
-    let mut pc_to_cost = HashMap::new();
+    let reverse_flow = reverse_edges(flow);
 
-    let method_body_cost = min_distance_to_statement(
-        pc,
-        &method,
-        coverage,
-        program,
-        flow,
-        st,
-        &mut pc_to_cost,
-        cache,
-        visited,
-    );
-
-    dbg!(&method.method_name, &method_body_cost);
-    let method_body_cost = match method_body_cost {
-        CumulativeCost::Cost(d) => {
-            cleanup_pc_to_cost(&method.method_name, &mut pc_to_cost, d);
-            method_body_cost
-        },
-        _ => {
-            // we may be able to solve it.
-            // if the only unexplored method call is the current function
-            // we can substitute that with infinity and solve
-
-            fn check_for_reducability(c: &CumulativeCost, current_method: &MethodIdentifier) -> bool {
-                match c {
-                    CumulativeCost::Cost(_) => true,
-                    CumulativeCost::Cycle(_, _) => true,
-                    CumulativeCost::Plus(c1, c2) => check_for_reducability(c1, current_method) && check_for_reducability(c2, current_method),
-                    CumulativeCost::UnexploredMethodCall(m) => current_method.method_name == m,
-                    CumulativeCost::Minimal(c1, c2) => check_for_reducability(c1, current_method) && check_for_reducability(c2, current_method),
-                }
-            }
+    let mut worklist: VecDeque<ProgramCounter> = newly_covered.iter().copied().collect();
+    let mut visited = HashSet::new();
 
-            if check_for_reducability(&method_body_cost, &method) {
-                fn reduce(c: &CumulativeCost) -> Option<Distance> {
-                    match c {
-                        CumulativeCost::Cost(d) => Some(*d),
-                        CumulativeCost::Plus(c1, c2) => {
-                            let d1 = reduce(&c1);
-                            let d2 = reduce(&c2);
-                            match (d1, d2) {
-                                (Some(d1), Some(d2)) => Some(Distance {
-                                    distance_type: std::cmp::min(d1.distance_type, d2.distance_type),
-                                    value: d1.value + d2.value,
-                                }),
-                                _ => None
-                            }
-                        }
-                        CumulativeCost::UnexploredMethodCall(_) => None,
-                        CumulativeCost::Minimal(c1, c2) => {
-                            let d1 = reduce(&c1);
-                            let d2 = reduce(&c2);
-                            match (d1, d2) {
-                                (Some(d1), Some(d2)) => Some(std::cmp::min(d1, d2)),
-                                (Some(d1), None) => Some(d1),
-                                (None, Some(d2)) => Some(d2),
-                                _ => None
-                            }
-                        },
-                        _ => unreachable!()
-                    }
-                }
-                let d = reduce(&method_body_cost).unwrap();
+    while let Some(pc) = worklist.pop_front() {
+        if !visited.insert(pc) {
+            continue;
+        }
+        pc_to_distance.remove(&pc);
 
-                cleanup_pc_to_cost(&method.method_name, &mut pc_to_cost, d);
-                CumulativeCost::Cost(d)
-            } else {
-                method_body_cost
-            }
-        },
-    };
+        if let Some(predecessors) = reverse_flow.get(&pc) {
+            worklist.extend(predecessors.iter().copied());
+        }
+    }
 
-    cache.insert(method, method_body_cost.clone());
+    cache.retain(|_, entry| !entry.pc_to_distance.keys().any(|pc| visited.contains(pc)));
+}
 
-    (method_body_cost, pc_to_cost)
+/// Computes, for every program counter reachable from `method`'s entry (including through
+/// calls into other methods), the minimal distance to the nearest uncovered statement, or
+/// else to the end of its own method.
+///
+/// Implemented as a classic backward dataflow fixpoint, Kildall-style, over the
+/// interprocedural CFG: the lattice is `Distance` with join `min` (a `ToFirstUncovered`
+/// distance always beats a `ToEndOfMethod` one, then smaller `value` wins), nodes are
+/// initialized to `TOP` except uncovered statements and `FunctionExit`s, and the transfer
+/// function is `d[pc] = cost(pc) + min_{succ} d[succ]`, where a method-call statement's
+/// `cost` is `1 + min` over its resolved callees' own entry-pc distances. The worklist is
+/// seeded from the base cases and propagated backward along `flow` and, for call targets,
+/// backward along the call edges too, so a change in a callee's summary re-triggers every
+/// caller that depends on it. Because the transfer is monotone (`Distance` only decreases
+/// toward the lattice's bottom) and the lattice has finite height, this converges for any
+/// CFG shape, including nested loops and direct or mutual recursion, without needing the
+/// ad-hoc cycle/recursion bookkeeping a DFS-based traversal requires.
+pub(in crate::exec::heuristics) fn min_distance_to_uncovered_method<'a>(
+    method: MethodIdentifier<'a>,
+    coverage: &HashMap<ProgramCounter, usize>,
+    program: &'a HashMap<ProgramCounter, CFGStatement>,
+    flow: &HashMap<ProgramCounter, Vec<ProgramCounter>>,
+    st: &SymbolTable,
+    cache: &mut Cache<'a>,
+) -> (Distance, HashMap<ProgramCounter, Distance>, HashMap<ProgramCounter, ProgramCounter>) {
+    min_distance_to_uncovered_method_with_cost_model(method, coverage, program, flow, st, cache, &UniformCostModel)
 }
 
-/// Computes the minimal distance to uncovered methods for all program counters in this method
-/// Recursively computes the minimal distance for any method calls referenced.
-fn min_distance_to_statement<'a>(
-    pc: ProgramCounter,
-    method: &MethodIdentifier<'a>,
+/// As [`min_distance_to_uncovered_method`], but charging `cost_model`'s weights instead of
+/// a uniform cost of 1 per statement/call hop.
+///
+/// Coverage typically changes by only a handful of pcs between symbolic-execution steps, so
+/// before running the fixpoint this checks `cache` for a still-fresh solution: one whose
+/// stored fingerprint of `method`'s own covered pcs, and of every transitive callee it
+/// depended on, still matches the current coverage. A hit returns the cached map directly,
+/// bit-identical to what a full recomputation would produce, since nothing it was built from
+/// has changed.
+///
+/// Also returns, for every pc with a genuine `ToFirstUncovered` distance, the nearest
+/// dominating branch on the way to it (see [`nearest_dominating_branches`]) — the decision
+/// point a directed search strategy should steer towards first, since it's what actually
+/// has to be taken to make progress, rather than the (possibly much further away) uncovered
+/// statement itself.
+pub(in crate::exec::heuristics) fn min_distance_to_uncovered_method_with_cost_model<'a>(
+    method: MethodIdentifier<'a>,
     coverage: &HashMap<ProgramCounter, usize>,
     program: &'a HashMap<ProgramCounter, CFGStatement>,
     flow: &HashMap<ProgramCounter, Vec<ProgramCounter>>,
     st: &SymbolTable,
-    pc_to_cost: &mut HashMap<ProgramCounter, CumulativeCost>,
     cache: &mut Cache<'a>,
-    visited: &mut HashSet<ProgramCounter>,
-) -> CumulativeCost {
-    let statement = &program[&pc];
-    dbg!(statement);
-    visited.insert(pc);
-
-    if pc_to_cost.contains_key(&pc) {
-        return pc_to_cost[&pc].clone();
-    }
+    cost_model: &dyn CostModel,
+) -> (Distance, HashMap<ProgramCounter, Distance>, HashMap<ProgramCounter, ProgramCounter>) {
+    let own_fingerprint = fingerprint_of_method(method, coverage, program, flow, st);
+
+    if let Some(cached) = cache.get(&method) {
+        let still_fresh = cached.fingerprint == own_fingerprint
+            && cached
+                .dependency_fingerprints
+                .iter()
+                .all(|(&dependency, &stored)| fingerprint_of_method(dependency, coverage, program, flow, st) == stored);
 
-    if let CFGStatement::FunctionExit { .. } = statement {
-        // We have reached the end of the method
-        let distance = if !coverage.contains_key(&pc) {
-            // Uncovered statement, has strict 1 cost
-            Distance {
-                value: 1,
-                distance_type: DistanceType::ToFirstUncovered,
-            }
-        } else {
-            Distance {
-                value: 1,
-                distance_type: DistanceType::ToEndOfMethod,
-            }
-        };
-        pc_to_cost.insert(pc, CumulativeCost::Cost(distance));
-        return CumulativeCost::Cost(distance);
+        if still_fresh {
+            return (cached.entry_distance, cached.pc_to_distance.clone(), cached.nearest_branches.clone());
+        }
     }
 
-    let next_pcs = &flow[&pc];
-    let remaining_cost = match &next_pcs[..] {
-        [] => unreachable!(),
-        multiple => {
-            // next cost is the minimum cost of following methods.
-            let next_cost = multiple
-                .iter()
-                .map(|next_pc| {
-                    if let CFGStatement::While(_, _) = &program[&next_pc] {
-                        if visited.contains(next_pc) {
-                            return CumulativeCost::Cycle(*next_pc, 0);
-                        }
-                    }
-                    // Cycle detected (while loop or recursive function)
-
-                    min_distance_to_statement(
-                        *next_pc, &method, coverage, program, flow, st, pc_to_cost, cache, visited,
-                    )
-                })
-                .reduce(minimize)
-                .expect("multiple pcs");
-            next_cost
-        }
-    };
+    let entry_pc = find_entry_for_static_invocation(
+        method.decl_name,
+        method.method_name,
+        method.arg_list.iter().cloned(),
+        program,
+        st,
+    );
 
-    // Find the cost of the current statement
-    let cost_of_this_statement =
-        statement_cost(pc, coverage, program, flow, st, pc_to_cost, cache, visited);
-
-    match cost_of_this_statement.clone() {
-        CumulativeCost::Cost(Distance {
-            value,
-            distance_type,
-        }) => {
-            let cost = if distance_type == DistanceType::ToEndOfMethod {
-                // We have to add the cost of the remainder of the current method.
-                let cost = remaining_cost.plus(value);
-
-                // if this is a while statement, check all cycles and fix them
-                if let CFGStatement::While(_, _) = &statement {
-                    fix_cycles(pc, cost.clone(), pc_to_cost);
-                }
-                cost
-            } else {
-                // We can short-circuit back since an uncovered statement was encountered.
-                cost_of_this_statement
-            };
-            pc_to_cost.insert(pc, cost.clone());
-            return cost;
-        }
-        CumulativeCost::Plus(_, _) => {
-            let cost =
-                CumulativeCost::Plus(Box::new(cost_of_this_statement), Box::new(remaining_cost));
+    let (nodes, call_targets, dependencies) = discover_interprocedural_cfg(entry_pc, program, flow, st);
+    let reverse_flow = reverse_edges(flow);
+    let reverse_call = reverse_call_edges(&call_targets);
+    let sccs = tarjan_scc(&nodes, flow);
 
-            pc_to_cost.insert(pc, cost.clone());
-            return cost;
-        }
-        CumulativeCost::UnexploredMethodCall(_) => {
-            let cost =
-                CumulativeCost::Plus(Box::new(cost_of_this_statement), Box::new(remaining_cost));
-            pc_to_cost.insert(pc, cost.clone());
-            return cost;
-        }
-        CumulativeCost::Cycle(_pc, _pluscost) => unimplemented!(),
+    let mut d: HashMap<ProgramCounter, Distance> = nodes.iter().map(|&pc| (pc, TOP)).collect();
 
-        CumulativeCost::Minimal(_, _) => {
-            let cost =
-                CumulativeCost::Plus(Box::new(cost_of_this_statement), Box::new(remaining_cost));
-            pc_to_cost.insert(pc, cost.clone());
-            return cost;
+    let mut worklist: VecDeque<ProgramCounter> = VecDeque::new();
+    for &pc in &nodes {
+        if !coverage.contains_key(&pc) || matches!(&program[&pc], CFGStatement::FunctionExit { .. }) {
+            worklist.push_back(pc);
         }
     }
-}
 
-fn cleanup_pc_to_cost<'a>(
-    method_name: &str,
-    pc_to_cost: &'a mut HashMap<ProgramCounter, CumulativeCost>,
-    resulting_cost: Distance,
-) {
-    let mut temp = HashMap::new();
-    std::mem::swap(pc_to_cost, &mut temp);
+    while let Some(pc) = worklist.pop_front() {
+        let relaxed = transfer(pc, coverage, program, flow, &call_targets, &d, cost_model, &sccs);
 
-    *pc_to_cost = temp
-        .into_iter()
-        .map(|(key, value)| {
-            (
-                key,
-                replace_method_call_in_costs(method_name, value, resulting_cost),
-            )
-        })
-        .collect();
-}
+        if relaxed < d[&pc] {
+            d.insert(pc, relaxed);
 
-fn replace_method_call_in_costs<'a>(
-    method_name: &str,
-    cost: CumulativeCost,
-    resulting_cost: Distance,
-) -> CumulativeCost {
-    match cost {
-        CumulativeCost::Plus(ref c1, ref c2) => {
-            let c1 = replace_method_call_in_costs(method_name, *c1.clone(), resulting_cost);
-            let c2 = replace_method_call_in_costs(method_name, *c2.clone(), resulting_cost);
-            match (c1, c2) {
-                (CumulativeCost::Cost(d1), CumulativeCost::Cost(d2)) => {
-                    CumulativeCost::Cost(Distance {
-                        distance_type: std::cmp::min(d1.distance_type, d2.distance_type),
-                        value: d1.value + d2.value,
-                    })
-                }
-                (c1, c2) => cost,
+            for &predecessor in reverse_flow.get(&pc).into_iter().flatten() {
+                worklist.push_back(predecessor);
             }
-        }
-        CumulativeCost::UnexploredMethodCall(method) if method_name == &method => {
-            CumulativeCost::Cost(resulting_cost)
-        }
-        CumulativeCost::Cycle(_, _) => todo!(),
-        CumulativeCost::Minimal(c1, c2) => {
-            let c1 = replace_method_call_in_costs(method_name, *c1, resulting_cost);
-            let c2 = replace_method_call_in_costs(method_name, *c2, resulting_cost);
-            match (c1, c2) {
-                (CumulativeCost::Cost(d1), CumulativeCost::Cost(d2)) => {
-                    CumulativeCost::Cost(std::cmp::min(d1, d2))
-                }
-                (c1, c2) => CumulativeCost::Plus(Box::new(c1), Box::new(c2)), // (c1, c2) => todo!("{:?} {:?}", c1, c2),
+            for &call_site in reverse_call.get(&pc).into_iter().flatten() {
+                worklist.push_back(call_site);
             }
         }
-        cost => cost,
     }
+
+    let entry_distance = d[&entry_pc];
+    let nearest_branches = nearest_dominating_branches(method, program, flow, st, &d);
+
+    let dependency_fingerprints = dependencies
+        .into_iter()
+        .map(|dependency| (dependency, fingerprint_of_method(dependency, coverage, program, flow, st)))
+        .collect();
+
+    cache.insert(
+        method,
+        CacheEntry {
+            fingerprint: own_fingerprint,
+            dependency_fingerprints,
+            entry_distance,
+            pc_to_distance: d.clone(),
+            nearest_branches: nearest_branches.clone(),
+        },
+    );
+
+    (entry_distance, d, nearest_branches)
+}
+
+/// For every pc in `pc_to_distance` with a genuine `ToFirstUncovered` distance, finds the
+/// nearest dominating branch that must be taken to reach its target — the decision point a
+/// directed search strategy should steer towards first, rather than the (possibly much
+/// further away) uncovered statement itself. Pcs belonging to a different method (reached
+/// only through a call edge, and thus outside `method`'s own dominator tree) are skipped.
+pub(in crate::exec::heuristics) fn nearest_dominating_branches(
+    method: MethodIdentifier,
+    program: &HashMap<ProgramCounter, CFGStatement>,
+    flow: &HashMap<ProgramCounter, Vec<ProgramCounter>>,
+    st: &SymbolTable,
+    pc_to_distance: &HashMap<ProgramCounter, Distance>,
+) -> HashMap<ProgramCounter, ProgramCounter> {
+    let tree = dominators(method, program, flow, st);
+
+    pc_to_distance
+        .iter()
+        .filter(|(_, distance)| distance.distance_type == DistanceType::ToFirstUncovered)
+        .filter_map(|(&pc, _)| tree.nearest_dominating_branch(pc, flow).map(|branch| (pc, branch)))
+        .collect()
 }
 
-fn fix_cycles(
+/// The dataflow transfer function for a single pc: the cost of executing `pc` itself,
+/// combined with the minimal distance of whatever it flows into.
+fn transfer(
     pc: ProgramCounter,
-    resulting_cost: CumulativeCost,
-    pc_to_cost: &mut HashMap<ProgramCounter, CumulativeCost>,
-) {
-    let mut to_repair = Vec::new();
+    coverage: &HashMap<ProgramCounter, usize>,
+    program: &HashMap<ProgramCounter, CFGStatement>,
+    flow: &HashMap<ProgramCounter, Vec<ProgramCounter>>,
+    call_targets: &HashMap<ProgramCounter, Vec<ProgramCounter>>,
+    d: &HashMap<ProgramCounter, Distance>,
+    cost_model: &dyn CostModel,
+    sccs: &StronglyConnectedComponents,
+) -> Distance {
+    if !coverage.contains_key(&pc) {
+        // An uncovered statement is itself the search target: its distance is 1,
+        // regardless of what follows.
+        return Distance {
+            distance_type: DistanceType::ToFirstUncovered,
+            value: 1,
+        };
+    }
 
-    for (k, v) in pc_to_cost.iter() {
-        if let CumulativeCost::Cycle(cycle_pc, cost) = v {
-            if pc == *cycle_pc {
-                to_repair.push((*k, *cost));
-            }
+    let statement_cost = if let Some(targets) = call_targets.get(&pc) {
+        // A call costs its own statement cost, plus a dispatch penalty proportional to the
+        // number of resolved targets, plus whichever resolved callee is cheapest to enter.
+        let cost = cost_model.statement_cost(&program[&pc]) + cost_model.call_cost(targets.len());
+        targets
+            .iter()
+            .map(|target| d[target])
+            .min()
+            .unwrap_or(TOP)
+            .plus(cost)
+    } else {
+        Distance {
+            distance_type: DistanceType::ToEndOfMethod,
+            value: cost_model.statement_cost(&program[&pc]),
         }
+    };
+
+    if statement_cost.distance_type == DistanceType::ToFirstUncovered {
+        // The target lies inside this statement's call (or is this statement), so the
+        // remainder of the current method is irrelevant.
+        return statement_cost;
     }
 
-    for (k, cost) in to_repair {
-        pc_to_cost.insert(k, resulting_cost.clone().plus(cost));
+    match &program[&pc] {
+        CFGStatement::FunctionExit { .. } => statement_cost,
+        _ => flow
+            .get(&pc)
+            .into_iter()
+            .flatten()
+            .map(|&successor| {
+                // Relaxing across a back-edge re-enters a loop, so it's charged an extra
+                // penalty on top of the successor's own distance, biasing the heuristic
+                // towards targets that need fewer loop iterations to reach.
+                if sccs.is_back_edge(pc, successor) {
+                    d[&successor].plus(cost_model.back_edge_penalty())
+                } else {
+                    d[&successor]
+                }
+            })
+            .min()
+            .unwrap_or(TOP)
+            .plus(statement_cost.value),
     }
 }
 
-/// Returns the cost of exploring the statement
-/// Can be either strictly in case of a found uncovered statement, or at least cost otherwise.
-fn statement_cost<'a>(
-    pc: ProgramCounter,
-    coverage: &HashMap<ProgramCounter, usize>,
+/// Discovers every program counter reachable from `entry_pc`, following `flow` within a
+/// method and, at each call statement, also descending into every resolved callee's own
+/// entry pc. Returns the discovered node set along with a map from each call-site pc to
+/// the entry pcs of the methods it may invoke.
+fn discover_interprocedural_cfg<'a>(
+    entry_pc: ProgramCounter,
     program: &'a HashMap<ProgramCounter, CFGStatement>,
     flow: &HashMap<ProgramCounter, Vec<ProgramCounter>>,
     st: &SymbolTable,
-    pc_to_cost: &mut HashMap<ProgramCounter, CumulativeCost>,
-    cache: &mut Cache<'a>,
-    visited: &mut HashSet<ProgramCounter>,
-) -> CumulativeCost {
-    let statement = &program[&pc];
+) -> (
+    HashSet<ProgramCounter>,
+    HashMap<ProgramCounter, Vec<ProgramCounter>>,
+    HashSet<MethodIdentifier<'a>>,
+) {
+    let mut nodes = HashSet::new();
+    let mut call_targets = HashMap::new();
+    let mut dependencies = HashSet::new();
+    let mut worklist = VecDeque::from([entry_pc]);
+
+    while let Some(pc) = worklist.pop_front() {
+        if !nodes.insert(pc) {
+            continue;
+        }
 
-    if !coverage.contains_key(&pc) {
-        // Uncovered statement, has strict 1 cost
-        CumulativeCost::Cost(Distance {
-            distance_type: DistanceType::ToFirstUncovered,
-            value: 1,
-        })
-    } else if let Some(invocation) = is_method_invocation(statement) {
-        // Case for a statement with an invocation.
-        // An invocation has more cost than a regular statement, the resulting cost is returned.
-        // If an unseen before method invocation is encountered, it will explore that first, and will add the results to the cache.
-        let methods_called = methods_called(invocation);
-
-        // Of all possible resolved methods, find the minimal cost to uncovered, or minimal cost to traverse.
-        let min_method_cost = methods_called
-            .into_iter()
-            .map(|method| {
-                // Check cache or compute cost for method
+        if let Some(invocation) = is_method_invocation(&program[&pc]) {
+            let callees = methods_called(invocation);
+            let targets = callees
+                .iter()
+                .map(|callee| {
+                    find_entry_for_static_invocation(callee.decl_name, callee.method_name, callee.arg_list.iter().cloned(), program, st)
+                })
+                .collect::<Vec<_>>();
 
-                let cost = if let Some(cost) = cache.get(&method) {
-                    cost.clone()
-                } else {
-                    // if method is already covered, but not in cache it means we are processing it currently.
-                    let next_pc = find_entry_for_static_invocation(
-                        method.decl_name,
-                        method.method_name,
-                        method.arg_list.iter().cloned(),
-                        program,
-                        st,
-                    );
-
-                    if visited.contains(&next_pc) {
-                        dbg!("oh oh recursion", next_pc);
-                        CumulativeCost::UnexploredMethodCall(method.method_name.to_string())
-                    } else {
-                        let (cost, method_pc_to_cost) =
-                            min_distance_to_uncovered_method_helper(
-                                method, coverage, program, flow, st, visited, cache,
-                            );
-
-                        pc_to_cost.extend(method_pc_to_cost);
-                        cost
-                    }
-                };
-                cost.increased_by_one()
-            })
-            .reduce(minimize)
-            .expect("at least one resolved method");
+            dependencies.extend(callees);
+            worklist.extend(targets.iter().copied());
+            call_targets.insert(pc, targets);
+        }
 
-        min_method_cost
-    } else {
-        // A normal statement has at least cost 1, to be added to remainder
-        CumulativeCost::Cost(Distance {
-            distance_type: DistanceType::ToEndOfMethod,
-            value: 1,
-        })
+        worklist.extend(flow.get(&pc).into_iter().flatten().copied());
+    }
+
+    (nodes, call_targets, dependencies)
+}
+
+fn reverse_edges(flow: &HashMap<ProgramCounter, Vec<ProgramCounter>>) -> HashMap<ProgramCounter, Vec<ProgramCounter>> {
+    let mut reverse: HashMap<ProgramCounter, Vec<ProgramCounter>> = HashMap::new();
+    for (&from, tos) in flow {
+        for &to in tos {
+            reverse.entry(to).or_default().push(from);
+        }
     }
+    reverse
 }
 
-fn minimize(a: CumulativeCost, b: CumulativeCost) -> CumulativeCost {
-     match (&a, &b) {
-        (CumulativeCost::Cost(a), CumulativeCost::Cost(b)) => CumulativeCost::Cost(std::cmp::min(a, b).clone()),
-        (CumulativeCost::Cycle(_, _), _) => b.clone(),
-        (_, CumulativeCost::Cycle(_, _)) => a.clone(),
-        (_, _) => CumulativeCost::Minimal(Box::new(a), Box::new(b)),
+fn reverse_call_edges(call_targets: &HashMap<ProgramCounter, Vec<ProgramCounter>>) -> HashMap<ProgramCounter, Vec<ProgramCounter>> {
+    let mut reverse: HashMap<ProgramCounter, Vec<ProgramCounter>> = HashMap::new();
+    for (&call_site, targets) in call_targets {
+        for &target in targets {
+            reverse.entry(target).or_default().push(call_site);
+        }
     }
+    reverse
 }
 
-fn is_method_invocation(statement: &CFGStatement) -> Option<&Invocation> {
+pub(in crate::exec::heuristics) fn is_method_invocation(statement: &CFGStatement) -> Option<&Invocation> {
     match statement {
-        CFGStatement::Statement(Statement::Call { invocation, .. })
-        | CFGStatement::Statement(Statement::Assign {
-            rhs: Rhs::RhsCall { invocation, .. },
+        CFGStatement::Statement(crate::Statement::Call { invocation, .. })
+        | CFGStatement::Statement(crate::Statement::Assign {
+            rhs: crate::Rhs::RhsCall { invocation, .. },
             ..
         }) => Some(invocation),
         _ => None,
@@ -531,14 +505,11 @@ fn is_method_invocation(statement: &CFGStatement) -> Option<&Invocation> {
 }
 
 /// Returns a list of methods that could be called at runtime depending on the runtimetype, by this invocation.
-fn methods_called(invocation: &Invocation) -> Vec<MethodIdentifier> {
+pub(in crate::exec::heuristics) fn methods_called(invocation: &Invocation) -> Vec<MethodIdentifier> {
     match invocation {
         Invocation::InvokeMethod { resolved, .. } => {
             // A regular method can resolve to multiple different methods due to dynamic dispatch, depending on the runtime type of the object.
             // We make here the assumption that any object can be represented and thus consider each resolved method.
-
-            // We also need to lookup the program counter for each method. (CANT WE DO THIS BEFOREHAND?)
-
             let methods = resolved.as_ref().unwrap();
 
             methods
@@ -611,7 +582,7 @@ mod tests {
         let (coverage, program, flows, symbol_table) = setup(path);
 
         let mut cache = Cache::new();
-        let (cost, pc_to_cost) = min_distance_to_uncovered_method(
+        let (cost, pc_to_cost, _) = min_distance_to_uncovered_method(
             MethodIdentifier {
                 method_name: "main",
                 decl_name: "Main",
@@ -651,7 +622,7 @@ mod tests {
         coverage.remove(&12);
 
         let mut cache = Cache::new();
-        let (cost, pc_to_cost) = min_distance_to_uncovered_method(
+        let (cost, pc_to_cost, _) = min_distance_to_uncovered_method(
             MethodIdentifier {
                 method_name: "main",
                 decl_name: "Main",
@@ -694,7 +665,7 @@ mod tests {
             arg_list: vec![RuntimeType::IntRuntimeType; 1],
         };
         let mut cache = Cache::new();
-        let (cost, pc_to_cost) = min_distance_to_uncovered_method(
+        let (cost, pc_to_cost, _) = min_distance_to_uncovered_method(
             method.clone(),
             &coverage,
             &program,
@@ -759,7 +730,7 @@ mod tests {
             arg_list: vec![RuntimeType::IntRuntimeType; 2],
         };
 
-        let (cost, pc_to_cost) = min_distance_to_uncovered_method(
+        let (cost, pc_to_cost, _) = min_distance_to_uncovered_method(
             entry_method.clone(),
             &coverage,
             &program,
@@ -835,7 +806,7 @@ mod tests {
             arg_list: vec![RuntimeType::IntRuntimeType; 2],
         };
 
-        let (cost, pc_to_cost) = min_distance_to_uncovered_method(
+        let (cost, pc_to_cost, _) = min_distance_to_uncovered_method(
             entry_method.clone(),
             &coverage,
             &program,
@@ -906,7 +877,7 @@ mod tests {
         let (coverage, program, flows, symbol_table) = setup(path);
 
         let mut cache = Cache::new();
-        let (cost, pc_to_cost) = min_distance_to_uncovered_method(
+        let (cost, pc_to_cost, _) = min_distance_to_uncovered_method(
             MethodIdentifier {
                 method_name: "main",
                 decl_name: "Main",
@@ -0,0 +1,133 @@
+use std::collections::{HashMap, HashSet};
+
+use super::ProgramCounter;
+
+/// The strongly-connected-component decomposition of a `flows` graph, restricted to a given
+/// node set. A component of size greater than one, or a singleton with a self-edge, is a
+/// loop; every edge that stays inside such a component and doesn't strictly advance the
+/// component's reverse-postorder is a back-edge re-entering the loop, rather than a normal
+/// acyclic step through it.
+#[derive(Debug, Clone)]
+pub(in crate::exec::heuristics) struct StronglyConnectedComponents {
+    component_of: HashMap<ProgramCounter, usize>,
+    loop_components: HashSet<usize>,
+    rpo_number: HashMap<ProgramCounter, usize>,
+}
+
+impl StronglyConnectedComponents {
+    /// Whether the edge `from -> to` is a back-edge: both endpoints lie in the same loop
+    /// component, and `to` doesn't come strictly later than `from` in reverse postorder
+    /// (i.e. it re-enters a part of the loop already passed through).
+    pub(in crate::exec::heuristics) fn is_back_edge(&self, from: ProgramCounter, to: ProgramCounter) -> bool {
+        let Some(&component) = self.component_of.get(&from) else {
+            return false;
+        };
+        if self.component_of.get(&to) != Some(&component) {
+            return false;
+        }
+        if !self.loop_components.contains(&component) {
+            return false;
+        }
+        self.rpo_number[&to] <= self.rpo_number[&from]
+    }
+}
+
+/// Computes the SCCs of `flow` restricted to `nodes`, using Tarjan's algorithm with an
+/// explicit work stack (rather than recursion) so it doesn't blow the stack on large methods.
+/// `nodes` may span several methods (e.g. an entire interprocedural node set); each
+/// connected piece is simply visited from whichever of its nodes is encountered first.
+///
+/// Each stack frame tracks the node being visited and an iterator position into its
+/// successor list, so a "recursive call" is simulated by pushing a new frame and a "return"
+/// by popping one and resuming the caller's iteration where it left off, exactly mirroring
+/// the textbook recursive algorithm's `index`/`lowlink`/`on_stack` bookkeeping.
+pub(in crate::exec::heuristics) fn tarjan_scc(
+    nodes: &HashSet<ProgramCounter>,
+    flow: &HashMap<ProgramCounter, Vec<ProgramCounter>>,
+) -> StronglyConnectedComponents {
+    let mut next_index = 0usize;
+    let mut index: HashMap<ProgramCounter, usize> = HashMap::new();
+    let mut lowlink: HashMap<ProgramCounter, usize> = HashMap::new();
+    let mut on_stack: HashSet<ProgramCounter> = HashSet::new();
+    let mut component_stack: Vec<ProgramCounter> = Vec::new();
+
+    let mut component_of: HashMap<ProgramCounter, usize> = HashMap::new();
+    let mut loop_components: HashSet<usize> = HashSet::new();
+    let mut next_component = 0usize;
+
+    // DFS finishing order across every root, reversed at the end into reverse postorder.
+    let mut finish_order: Vec<ProgramCounter> = Vec::new();
+
+    for &root in nodes {
+        if index.contains_key(&root) {
+            continue;
+        }
+
+        // Each work-stack frame is (node, position into its successor list).
+        let mut work: Vec<(ProgramCounter, usize)> = vec![(root, 0)];
+        index.insert(root, next_index);
+        lowlink.insert(root, next_index);
+        next_index += 1;
+        component_stack.push(root);
+        on_stack.insert(root);
+
+        while let Some(&mut (v, ref mut successor_pos)) = work.last_mut() {
+            let successors = flow.get(&v).map(Vec::as_slice).unwrap_or_default();
+
+            if *successor_pos < successors.len() {
+                let w = successors[*successor_pos];
+                *successor_pos += 1;
+
+                if !index.contains_key(&w) {
+                    index.insert(w, next_index);
+                    lowlink.insert(w, next_index);
+                    next_index += 1;
+                    component_stack.push(w);
+                    on_stack.insert(w);
+                    work.push((w, 0));
+                } else if on_stack.contains(&w) {
+                    let new_lowlink = lowlink[&v].min(index[&w]);
+                    lowlink.insert(v, new_lowlink);
+                }
+            } else {
+                // All successors processed: this is where the recursive algorithm would return.
+                if lowlink[&v] == index[&v] {
+                    let mut members = Vec::new();
+                    loop {
+                        let member = component_stack.pop().unwrap();
+                        on_stack.remove(&member);
+                        component_of.insert(member, next_component);
+                        members.push(member);
+                        if member == v {
+                            break;
+                        }
+                    }
+
+                    let is_loop = members.len() > 1
+                        || flow.get(&members[0]).into_iter().flatten().any(|&successor| successor == members[0]);
+                    if is_loop {
+                        loop_components.insert(next_component);
+                    }
+                    next_component += 1;
+                }
+
+                // Postorder: a node finishes only once every node reachable "below" it
+                // (later in the work stack) has been fully processed.
+                finish_order.push(v);
+                work.pop();
+                if let Some(&(parent, _)) = work.last() {
+                    let new_lowlink = lowlink[&parent].min(lowlink[&v]);
+                    lowlink.insert(parent, new_lowlink);
+                }
+            }
+        }
+    }
+
+    let rpo_number = finish_order.iter().rev().enumerate().map(|(number, &pc)| (pc, number)).collect();
+
+    StronglyConnectedComponents {
+        component_of,
+        loop_components,
+        rpo_number,
+    }
+}
@@ -1,46 +1,297 @@
-use std::collections::{HashMap, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
 
 use itertools::Itertools;
 
 use crate::{cfg::CFGStatement, Reference, Statement};
 
-use super::{eval_reference::ExecRef, Engine, State, ThreadState};
+use super::{eval_reference::ExecRef, por::Tid, Engine, State, ThreadState};
 
-struct ExecLock {}
+/// Whether a queued or granted lock request is shared (read) or exclusive (write).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum LockKind {
+    Read,
+    Write,
+}
+
+/// Which queued waiter(s) [`release`] enables once a lock frees up. `Fifo` and `Lifo` stay
+/// deterministic (arrival order and reverse-arrival order respectively); `Nondeterministic`
+/// forks a separate explored [`State`] per queued waiter so the engine covers every admissible
+/// handoff, catching starvation/lost-wakeup bugs that only show up under an unfair scheduler.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum LockPolicy {
+    Fifo,
+    Lifo,
+    Nondeterministic,
+}
+
+/// The state of a single lock, mirroring a standard rwlock: nobody holds it, some set of
+/// readers hold it concurrently, or a single writer holds it exclusively. `Reading` tracks the
+/// actual holding `Tid`s, not just a count, so `build_wait_for_graph` can tell a thread
+/// queued behind a read lock it itself already holds (always a self-deadlock on upgrade) apart
+/// from one queued behind other threads' reads (not necessarily a deadlock, since those threads
+/// can still independently release).
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum LockState {
+    Unlocked,
+    Reading(HashSet<Tid>),
+    Writing(Tid),
+}
+
+/// `(lock state, wait queue, write-lock recursion depth)`. The depth only ever applies to the
+/// current writer: Java-style `synchronized`/monitor semantics let the thread already holding a
+/// lock take it again without blocking on itself, so a nested `Lock`/`WriteLock` on a reference
+/// the active thread already owns increments the depth instead of queueing, and `Unlock` only
+/// actually frees the lock once the depth returns to zero.
+type LockEntry = (LockState, VecDeque<(Tid, LockKind)>, u32);
+
+/// Grants `lock` to `state.active_thread` as `kind` if it's immediately available, otherwise
+/// queues the request and disables the thread. A read succeeds against `Unlocked` or
+/// `Reading(_)`, unless a writer is already queued ahead of it (granting it anyway would starve
+/// that writer indefinitely under sustained read traffic); a write succeeds against `Unlocked`.
+/// Either kind reenters against `Writing(tid)` when `tid` is the active thread itself — a thread
+/// already holding the write lock can always take it again, or read through it, without queueing
+/// behind itself.
+fn acquire(state: &mut State, lock: Reference, kind: LockKind) {
+    let entry = state.lock_requests.entry(lock).or_insert_with(|| (LockState::Unlocked, VecDeque::new(), 0));
+
+    if entry.0 == LockState::Writing(state.active_thread) {
+        entry.2 += 1;
+        return;
+    }
+
+    let front_is_writer = matches!(entry.1.front(), Some(&(_, LockKind::Write)));
+    let can_acquire = match kind {
+        LockKind::Read => matches!(entry.0, LockState::Unlocked | LockState::Reading(_)) && !front_is_writer,
+        LockKind::Write => matches!(entry.0, LockState::Unlocked),
+    };
+
+    if can_acquire {
+        entry.0 = match (entry.0.clone(), kind) {
+            (LockState::Reading(mut readers), LockKind::Read) => {
+                readers.insert(state.active_thread);
+                LockState::Reading(readers)
+            }
+            (_, LockKind::Read) => LockState::Reading(HashSet::from([state.active_thread])),
+            (_, LockKind::Write) => {
+                entry.2 = 1;
+                LockState::Writing(state.active_thread)
+            }
+        };
+    } else {
+        entry.1.push_back((state.active_thread, kind));
+        state.threads.get_mut(&state.active_thread).unwrap().state = ThreadState::Disabled;
+    }
+}
+
+/// Releases `state.active_thread`'s hold on `lock`: decrements the reader count or the writer's
+/// recursion depth, and once that reaches zero, wakes the next waiter(s) per `policy` without
+/// granting them the lock outright, leaving each woken thread to actually acquire it the next
+/// time it's scheduled, same as the original exclusive lock's handoff. Under `Fifo`/`Lifo` this
+/// mutates `state` in place; under `Nondeterministic` it wakes the first queued waiter in place
+/// and forks one new explored state per remaining waiter via `en`, so the engine covers every
+/// admissible handoff instead of
+/// baking in a single wakeup order. Whichever thread the in-place `state` wakes is recorded onto
+/// its `wakeups`, a log of handoffs kept separate from `path`: a woken thread is only enabled to
+/// contend for the lock, not actually scheduled, so it hasn't executed anything at `branch_pc`
+/// and splicing it into `path` would fabricate a step in the counterexample trace.
+fn release(state: &mut State, lock: Reference, policy: LockPolicy, en: &mut impl Engine) {
+    let Some((lock_state, _, depth)) = state.lock_requests.get_mut(&lock) else {
+        return;
+    };
+
+    let now_free = match lock_state {
+        LockState::Reading(readers) => {
+            readers.remove(&state.active_thread);
+            readers.is_empty()
+        }
+        LockState::Writing(_) => {
+            *depth = depth.saturating_sub(1);
+            *depth == 0
+        }
+        LockState::Unlocked => true,
+    };
+    if !now_free {
+        return;
+    }
+    state.lock_requests.get_mut(&lock).unwrap().0 = LockState::Unlocked;
+
+    let branch_pc = state.threads[&state.active_thread].pc;
+    let wakeable = wakeable_sets(&state.lock_requests[&lock].1, policy);
+    let Some((first, rest)) = wakeable.split_first() else {
+        return;
+    };
+
+    for woken in rest {
+        let mut forked = state.clone();
+        wake(&mut forked, lock, woken, branch_pc);
+        en.add_state(forked);
+    }
+    wake(state, lock, first, branch_pc);
+}
 
-impl ExecRef for ExecLock {
-    fn exec_over_ref(state: &mut State, ref_: &Reference) {
-        if let Some(_) = state.lock_requests.get(ref_) {
-            state.lock_requests.get_mut(ref_).unwrap().push_back(state.active_thread);
-            state.threads.get_mut(&state.active_thread).unwrap().state = ThreadState::Disabled;
-        } else {
-            state.lock_requests.insert(*ref_, VecDeque::new());
+/// The distinct candidate wakeup sets `release` can choose between for `policy`: under
+/// `Fifo`/`Lifo` there's exactly one (the deterministic choice), under `Nondeterministic` one
+/// per queued waiter, since any of them is an admissible next owner once the lock is free.
+fn wakeable_sets(queue: &VecDeque<(Tid, LockKind)>, policy: LockPolicy) -> Vec<Vec<(Tid, LockKind)>> {
+    match policy {
+        LockPolicy::Fifo => contiguous_run(queue.iter().copied()).into_iter().collect(),
+        LockPolicy::Lifo => contiguous_run(queue.iter().rev().copied()).into_iter().collect(),
+        LockPolicy::Nondeterministic => queue.iter().map(|&entry| vec![entry]).collect(),
+    }
+}
+
+/// A single writer, or the run of readers sharing its front of `waiters`, whichever comes first.
+fn contiguous_run(mut waiters: impl Iterator<Item = (Tid, LockKind)>) -> Option<Vec<(Tid, LockKind)>> {
+    match waiters.next()? {
+        entry @ (_, LockKind::Write) => Some(vec![entry]),
+        entry @ (_, LockKind::Read) => {
+            let mut run = vec![entry];
+            run.extend(waiters.take_while(|&(_, kind)| kind == LockKind::Read));
+            Some(run)
         }
     }
 }
 
+/// Removes every `(tid, _)` in `woken` from `lock`'s wait queue and enables those threads,
+/// recording the handoff in `state.wakeups` (not `state.path`: the woken thread hasn't executed
+/// anything yet, it's merely eligible to be scheduled next).
+fn wake(state: &mut State, lock: Reference, woken: &[(Tid, LockKind)], branch_pc: u64) {
+    let queue = &mut state.lock_requests.get_mut(&lock).unwrap().1;
+    queue.retain(|entry| !woken.contains(entry));
+    for &(tid, _) in woken {
+        state.threads.get_mut(&tid).unwrap().state = ThreadState::Enabled;
+        state.wakeups.push((tid, lock, branch_pc));
+    }
+}
+
+struct ExecReadLock {}
+
+impl ExecRef for ExecReadLock {
+    fn exec_over_ref(state: &mut State, ref_: &Reference, _en: &mut impl Engine) {
+        acquire(state, *ref_, LockKind::Read);
+    }
+}
+
+struct ExecWriteLock {}
+
+impl ExecRef for ExecWriteLock {
+    fn exec_over_ref(state: &mut State, ref_: &Reference, _en: &mut impl Engine) {
+        acquire(state, *ref_, LockKind::Write);
+    }
+}
+
 struct ExecUnlock {}
 
 impl ExecRef for ExecUnlock {
-    fn exec_over_ref(state: &mut State, ref_: &Reference) {
-        if let Some(requests) = state.lock_requests.remove(ref_) {
-            for request in requests {
-                state.threads.get_mut(&request).unwrap().state = ThreadState::Enabled;
+    fn exec_over_ref(state: &mut State, ref_: &Reference, en: &mut impl Engine) {
+        let policy = en.options().lock_policy;
+        release(state, *ref_, policy, en);
+    }
+}
+
+/// A deadlock cycle found in the wait-for graph: threads `[t0, t1, .., tn]` where each `ti`
+/// is blocked waiting on `locks[i]`, which is held by `t(i+1) mod n`.
+#[derive(Debug, Clone)]
+pub(super) struct Deadlock {
+    pub(super) threads: Vec<Tid>,
+    pub(super) locks: Vec<Reference>,
+}
+
+/// Builds the wait-for graph: an edge `waiting -> (owner, lock)` for every thread queued on
+/// `lock` while it's exclusively held, plus a self-loop `waiting -> (waiting, lock)` for a
+/// thread queued on `lock` while it's among the current readers — that's a lock upgrade
+/// (`ReadLock` then `WriteLock`/`Lock` on the same reference) and it can never succeed on its
+/// own: the queued thread is disabled, so it can't release its own read hold, and the reader
+/// count can never reach zero through other readers alone while it's still held. Beyond that,
+/// `Reading(_)` still can't be attributed to a single concrete owner when the waiter isn't one
+/// of the readers itself, so a reader set deadlocking against a queued writer in principle won't
+/// be reported until the lock is next held exclusively.
+fn build_wait_for_graph(state: &State) -> HashMap<Tid, Vec<(Tid, Reference)>> {
+    let mut edges: HashMap<Tid, Vec<(Tid, Reference)>> = HashMap::new();
+    for (&lock, (lock_state, queue, _depth)) in &state.lock_requests {
+        match lock_state {
+            LockState::Writing(owner) => {
+                for &(waiting, _) in queue {
+                    edges.entry(waiting).or_default().push((*owner, lock));
+                }
             }
+            LockState::Reading(readers) => {
+                for &(waiting, _) in queue {
+                    if readers.contains(&waiting) {
+                        edges.entry(waiting).or_default().push((waiting, lock));
+                    }
+                }
+            }
+            LockState::Unlocked => {}
         }
     }
+    edges
 }
 
-pub(super) fn check_deadlock(
-    state: &State
-) {
-    if !state.threads.values().any(|t| t.state == ThreadState::Enabled) 
-    && !(state.threads[&0].state == ThreadState::Finished) 
-    && !state.threads.values().any(|t| t.state == ThreadState::Excepted) { 
-        println!("{:?}", state.threads.values().map(|t| (t.tid, t.state.clone())).collect_vec());
-        println!("{:?}", state.path);
-        unreachable!("DEADLOCK");
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Color {
+    White,
+    Gray,
+    Black,
+}
+
+/// Iterative DFS over `edges` with three-color marking and an explicit recursion stack:
+/// pushing a node already Gray (still on the stack, not yet fully explored) means the stack
+/// suffix from that node onward is a cycle, which is exactly a deadlock in the wait-for graph.
+fn find_cycle(edges: &HashMap<Tid, Vec<(Tid, Reference)>>) -> Option<Deadlock> {
+    let mut color: HashMap<Tid, Color> = HashMap::new();
+
+    for &start in edges.keys().sorted().collect_vec().iter() {
+        if color.get(start).copied().unwrap_or(Color::White) != Color::White {
+            continue;
+        }
+
+        // Stack entries are (node, index of the next outgoing edge to explore, lock that was
+        // waited on to reach this node from its predecessor on the stack).
+        let mut stack: Vec<(Tid, usize, Option<Reference>)> = vec![(*start, 0, None)];
+        color.insert(*start, Color::Gray);
+
+        while let Some(&(node, edge_idx, _)) = stack.last() {
+            let outgoing = edges.get(&node).map(Vec::as_slice).unwrap_or(&[]);
+            if edge_idx >= outgoing.len() {
+                color.insert(node, Color::Black);
+                stack.pop();
+                continue;
+            }
+
+            let (next, lock) = outgoing[edge_idx];
+            stack.last_mut().unwrap().1 += 1;
+
+            match color.get(&next).copied().unwrap_or(Color::White) {
+                Color::White => {
+                    color.insert(next, Color::Gray);
+                    stack.push((next, 0, Some(lock)));
+                }
+                Color::Gray => {
+                    let cycle_start = stack.iter().position(|&(tid, ..)| tid == next).unwrap();
+                    let threads = stack[cycle_start..].iter().map(|&(tid, ..)| tid).collect_vec();
+                    let mut locks = stack[cycle_start + 1..]
+                        .iter()
+                        .map(|&(_, _, entry_lock)| entry_lock.unwrap())
+                        .collect_vec();
+                    locks.push(lock);
+                    return Some(Deadlock { threads, locks });
+                }
+                Color::Black => {}
+            }
+        }
     }
+
+    None
+}
+
+/// Detects a deadlock (a cycle in the wait-for graph) among the currently blocked threads,
+/// rather than only firing when every thread happens to be stuck at once: a cycle of two
+/// threads waiting on each other is a real deadlock even while a third thread is still making
+/// progress. The caller is responsible for turning a `Some` into a reported counterexample and
+/// failing the verification, the same way it does for any other `ActionResult` violation.
+pub(super) fn check_deadlock(state: &State) -> Option<Deadlock> {
+    find_cycle(&build_wait_for_graph(state))
 }
 
 pub(super) fn update_next_locks(
@@ -48,8 +299,14 @@ pub(super) fn update_next_locks(
     statement: &CFGStatement,
     en: &mut impl Engine
 ) {
-    if let CFGStatement::Statement(Statement::Lock { identifier, .. }) = statement {
-        ExecLock::exec(state, identifier, en)
+    // The plain `Lock` statement predates read/write locks and keeps its original exclusive
+    // semantics, equivalent to `WriteLock`.
+    if let CFGStatement::Statement(Statement::Lock { identifier, .. } | Statement::WriteLock { identifier, .. }) = statement {
+        ExecWriteLock::exec(state, identifier, en)
+    }
+
+    if let CFGStatement::Statement(Statement::ReadLock { identifier, .. }) = statement {
+        ExecReadLock::exec(state, identifier, en)
     }
 
     if let CFGStatement::Statement(Statement::Unlock { identifier, .. }) = statement {
@@ -70,4 +327,55 @@ pub(super) fn update_joins(
             }
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edges(pairs: &[(Tid, Tid, Reference)]) -> HashMap<Tid, Vec<(Tid, Reference)>> {
+        let mut edges: HashMap<Tid, Vec<(Tid, Reference)>> = HashMap::new();
+        for &(from, to, lock) in pairs {
+            edges.entry(from).or_default().push((to, lock));
+        }
+        edges
+    }
+
+    #[test]
+    fn no_cycle_in_a_chain() {
+        // 1 waits on 2, 2 waits on 3: nobody waits back on 1, so no deadlock.
+        let graph = edges(&[(1, 2, 10), (2, 3, 11)]);
+        assert!(find_cycle(&graph).is_none());
+    }
+
+    #[test]
+    fn two_thread_cycle_is_detected() {
+        // 1 waits on the lock 2 holds, 2 waits on the lock 1 holds.
+        let graph = edges(&[(1, 2, 10), (2, 1, 11)]);
+        let deadlock = find_cycle(&graph).expect("two-thread cycle should be a deadlock");
+        assert_eq!(deadlock.threads.len(), 2);
+        assert!(deadlock.threads.contains(&1));
+        assert!(deadlock.threads.contains(&2));
+        assert_eq!(deadlock.locks.len(), 2);
+    }
+
+    #[test]
+    fn three_thread_cycle_is_detected() {
+        let graph = edges(&[(1, 2, 10), (2, 3, 11), (3, 1, 12)]);
+        let deadlock = find_cycle(&graph).expect("three-thread cycle should be a deadlock");
+        assert_eq!(deadlock.threads.len(), 3);
+        for tid in [1, 2, 3] {
+            assert!(deadlock.threads.contains(&tid));
+        }
+        assert_eq!(deadlock.locks.len(), 3);
+    }
+
+    #[test]
+    fn cycle_behind_an_acyclic_prefix_is_still_found() {
+        // 1 waits on 2 (a dead end), while 3 and 4 deadlock on each other independently.
+        let graph = edges(&[(1, 2, 10), (3, 4, 11), (4, 3, 12)]);
+        let deadlock = find_cycle(&graph).expect("independent cycle should still be found");
+        assert!(deadlock.threads.contains(&3));
+        assert!(deadlock.threads.contains(&4));
+    }
+}
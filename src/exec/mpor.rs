@@ -1,10 +1,13 @@
-use std::{collections::HashSet, rc::Rc};
+use std::{
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
 
 use itertools::Itertools;
 
 use crate::{cfg::CFGStatement, exec::ThreadState, stack::Stack, Expression, Identifier, Lhs, Reference, Rhs, Statement};
 
-use super::{eval::evaluate, eval_assertion, Access, AliasMap, Engine, EngineContext, State};
+use super::{eval::evaluate, eval_assertion, por::Tid, Access, AliasMap, Engine, EngineContext, State};
 
 
 pub(super) fn validate_quasi_monotonicity(state: &mut State, statement: CFGStatement, curr_statement: CFGStatement, en: &mut EngineContext) -> bool {
@@ -30,6 +33,84 @@ pub(super) fn validate_quasi_monotonicity(state: &mut State, statement: CFGState
     out
 }
 
+/// A Dynamic Partial Order Reduction alternative to [`validate_quasi_monotonicity`]'s tid-
+/// ordering heuristic, driven by the same [`has_access_conflicts`] dependency relation. Where
+/// the tid ordering over-explores whenever two independent transitions happen to be enabled
+/// in increasing-tid order, this schedules `state`'s active thread as usual and backtracks
+/// into exactly the other enabled threads whose next transition is dependent with it (and
+/// therefore can't be soundly deferred), skipping anything asleep in `state.sleep` — assumed
+/// a `HashSet<Tid>` field on [`State`] carried forward and refined per successor by
+/// [`successor_sleep_set`]. Includes the active thread whenever it is enabled; when it isn't,
+/// every enabled thread is returned instead, so the set is never empty while at least one
+/// thread is enabled.
+pub(super) fn dpor_backtrack_set(state: &mut State, program: &HashMap<u64, CFGStatement>, en: &mut EngineContext) -> Vec<Tid> {
+    let active = state.active_thread;
+
+    let enabled = state
+        .threads
+        .values()
+        .filter(|t| t.state == ThreadState::Enabled)
+        .map(|t| t.tid)
+        .collect::<Vec<_>>();
+
+    if !enabled.contains(&active) {
+        return enabled;
+    }
+
+    let statement = program[&state.threads[&active].pc].clone();
+    let mut active_accesses = get_all_accesses(state, statement.clone(), en);
+    active_accesses.extend(get_lock_accesses(state, statement, en));
+
+    let mut backtrack = vec![active];
+    for &tid in &enabled {
+        if tid == active || state.sleep.contains(&tid) {
+            continue;
+        }
+
+        let statement = program[&state.threads[&tid].pc].clone();
+        let mut tid_accesses = get_all_accesses(state, statement.clone(), en);
+        tid_accesses.extend(get_lock_accesses(state, statement, en));
+
+        if has_access_conflicts(state, en, active_accesses.clone(), tid_accesses) {
+            backtrack.push(tid);
+        }
+    }
+
+    backtrack
+}
+
+/// The sleep set a successor state should carry forward after scheduling `scheduled` out of
+/// `parent_sleep`: every thread already asleep that's still independent of `scheduled`'s
+/// access set stays asleep, since exploring `scheduled` first didn't reveal anything new
+/// about it; a thread dependent with `scheduled` is woken, since the new transition may have
+/// changed what it can observe. Called once per thread in [`dpor_backtrack_set`]'s returned
+/// set, in order, so earlier siblings accumulate into later ones' sleep set too — they were
+/// just explored as alternate orderings from this same node, so re-exploring them again from
+/// a later sibling would be redundant.
+pub(super) fn successor_sleep_set(
+    state: &mut State,
+    program: &HashMap<u64, CFGStatement>,
+    en: &mut EngineContext,
+    parent_sleep: &HashSet<Tid>,
+    scheduled: Tid,
+) -> HashSet<Tid> {
+    let statement = program[&state.threads[&scheduled].pc].clone();
+    let mut scheduled_accesses = get_all_accesses(state, statement.clone(), en);
+    scheduled_accesses.extend(get_lock_accesses(state, statement, en));
+
+    parent_sleep
+        .iter()
+        .copied()
+        .filter(|tid| state.threads.contains_key(tid))
+        .filter(|&tid| {
+            let statement = program[&state.threads[&tid].pc].clone();
+            let mut tid_accesses = get_all_accesses(state, statement.clone(), en);
+            tid_accesses.extend(get_lock_accesses(state, statement, en));
+            !has_access_conflicts(state, en, scheduled_accesses.clone(), tid_accesses)
+        })
+        .collect()
+}
+
 pub(super) fn get_all_accesses(state: &mut State, statement: CFGStatement, en: &mut impl Engine) -> Vec<Access> {
     let thread = state.threads[&state.active_thread].clone();
     let alias_map = state.alias_map.clone();
@@ -80,15 +161,29 @@ pub(super) fn get_all_accesses(state: &mut State, statement: CFGStatement, en: &
     }
 }
 
+/// The accesses a lock-related statement performs, consumed by [`has_access_conflicts`] to
+/// decide whether [`dpor_backtrack_set`]/[`successor_sleep_set`] need to treat it as racing
+/// against another thread's access. The backtrack/sleep-set DPOR machinery itself already
+/// existed (see [`dpor_backtrack_set`]/[`successor_sleep_set`]); what changed here is only
+/// this classification, after the reader/writer lock split gave `ReadLock`/`WriteLock` their
+/// own `Statement` variants instead of a single `Lock` — without it, a `ReadLock` matched
+/// neither arm below and produced no `Access` at all, so two concurrent readers would
+/// (correctly) look independent but so would a reader racing a writer.
 pub(super) fn get_lock_accesses(state: &mut State, statement: CFGStatement, en: &mut impl Engine) -> Vec<Access> {
     let thread = state.threads[&state.active_thread].clone();
     let alias_map = state.alias_map.clone();
     match statement {
-        CFGStatement::Statement(Statement::Lock { identifier, .. }) => {
+        // A plain `Lock` keeps its pre-rwlock exclusive semantics, same as `WriteLock`; `Unlock`
+        // is conservatively treated as exclusive too since it's always a synchronization point,
+        // even when releasing a lock only ever held for reading.
+        CFGStatement::Statement(Statement::Lock { identifier, .. } | Statement::WriteLock { identifier, .. } | Statement::Unlock { identifier, .. }) => {
             vec![Access::LockAction(get_heap_ref(identifier, thread.stack.clone(), alias_map.clone()))]
         },
-        CFGStatement::Statement(Statement::Unlock { identifier, .. }) => {
-            vec![Access::LockAction(get_heap_ref(identifier, thread.stack.clone(), alias_map.clone()))]
+        // Two concurrent read-locks on the same reference are independent, so they get their own
+        // access kind: `has_access_conflicts` only races a `LockRead` against a `LockAction`
+        // (write), never against another `LockRead`.
+        CFGStatement::Statement(Statement::ReadLock { identifier, .. }) => {
+            vec![Access::LockRead(get_heap_ref(identifier, thread.stack.clone(), alias_map.clone()))]
         },
         _ => vec![]
     }
@@ -128,7 +223,7 @@ pub(super) fn get_heap_ref(var: Identifier, stack: Stack, alias_map: AliasMap) -
    
 }
 
-fn has_access_conflicts(state: &mut State, en: &mut impl Engine, prev: Vec<Access>, curr: Vec<Access>) -> bool {
+pub(super) fn has_access_conflicts(state: &mut State, en: &mut impl Engine, prev: Vec<Access>, curr: Vec<Access>) -> bool {
     for x in prev.iter() {
         for y in curr.iter() {
             match x {
@@ -183,10 +278,21 @@ fn has_access_conflicts(state: &mut State, en: &mut impl Engine, prev: Vec<Acces
                     _ => {}
                 },
                 Access::LockAction(x1) => match y {
+                    Access::LockAction(y1) | Access::LockRead(y1) => {
+                        let conflicts: Vec<&i64> = x1.intersection(y1).collect_vec();
+                        if conflicts.len() > 0 {
+                            return true
+                        }
+                    },
+                    _ => {}
+                }
+                // Two read-locks on the same reference don't conflict; a read only races
+                // against an exclusive lock/unlock on the same reference.
+                Access::LockRead(x1) => match y {
                     Access::LockAction(y1) => {
                         let conflicts: Vec<&i64> = x1.intersection(y1).collect_vec();
-                        if conflicts.len() > 0 { 
-                            return true 
+                        if conflicts.len() > 0 {
+                            return true
                         }
                     },
                     _ => {}
@@ -0,0 +1,63 @@
+use std::collections::HashMap;
+
+use crate::cfg::CFGStatement;
+
+use super::{
+    mpor::{get_all_accesses, get_lock_accesses, has_access_conflicts},
+    EngineContext, State, ThreadState,
+};
+
+pub type Tid = u64;
+
+/// Computes a non-empty *ample set* of enabled thread ids to expand at `state`.
+///
+/// Implements the classic partial-order-reduction ample-set condition: a transition
+/// outside the chosen set must never be dependent on one inside it, where two
+/// transitions are dependent when [`has_access_conflicts`] reports a conflict between their
+/// next steps' [`Access`](super::mpor) sets (a shared heap location where at least one side
+/// writes, a lock action on the same reference, or a join on a finishing thread).
+/// Picking such a subset preserves every reachable assertion violation while pruning
+/// interleavings that are equivalent up to reordering of independent steps.
+///
+/// Only single-thread ample sets are tried: the first enabled thread that is independent of
+/// every other enabled thread is returned alone. If there is no such thread, the full set of
+/// enabled threads is returned; larger proper subsets are not searched.
+pub(super) fn ample_set(
+    state: &mut State,
+    program: &HashMap<u64, CFGStatement>,
+    en: &mut EngineContext,
+) -> Vec<Tid> {
+    let enabled = state
+        .threads
+        .values()
+        .filter(|t| t.state == ThreadState::Enabled)
+        .map(|t| t.tid)
+        .collect::<Vec<_>>();
+
+    if enabled.len() <= 1 {
+        return enabled;
+    }
+
+    let mut accesses = HashMap::new();
+    for &tid in &enabled {
+        let statement = program[&state.threads[&tid].pc].clone();
+        let mut tid_accesses = get_all_accesses(state, statement.clone(), en);
+        tid_accesses.extend(get_lock_accesses(state, statement, en));
+        accesses.insert(tid, tid_accesses);
+    }
+
+    for &candidate in &enabled {
+        let candidate_accesses = accesses[&candidate].clone();
+        let independent_of_all_others = enabled.iter().filter(|&&tid| tid != candidate).all(|&tid| {
+            !has_access_conflicts(state, en, candidate_accesses.clone(), accesses[&tid].clone())
+        });
+
+        if independent_of_all_others {
+            // Every other enabled thread is independent of `candidate`, so scheduling only
+            // `candidate` here is a safe ample set: nothing outside it can race with it.
+            return vec![candidate];
+        }
+    }
+
+    enabled
+}
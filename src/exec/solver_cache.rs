@@ -0,0 +1,211 @@
+use std::{
+    collections::HashMap,
+    fs::OpenOptions,
+    io::{BufReader, BufWriter, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use fs2::FileExt;
+use serde::{Deserialize, Serialize};
+
+use crate::{BinOp, Expression, Identifier};
+
+/// A 128-bit stable fingerprint of a canonicalized path-condition `Expression`.
+///
+/// Canonicalization sorts the operands of commutative operators and alpha-renames
+/// symbolic variables/`SymbolicRef` names in first-occurrence order, so structurally
+/// identical path conditions hash identically regardless of how they were built up.
+pub type Fingerprint = u128;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CachedSatResult {
+    Sat,
+    Unsat,
+}
+
+/// An on-disk, `flock`-guarded cache of Z3 results keyed by [`Fingerprint`], shared
+/// safely between concurrent `verify()` processes (e.g. the benchmark's repeated
+/// heuristic passes).
+pub struct SolverCache {
+    path: Option<PathBuf>,
+    entries: HashMap<Fingerprint, CachedSatResult>,
+}
+
+impl SolverCache {
+    /// Loads the cache from `path`, if given. A missing file is treated as an empty cache.
+    pub fn open(path: Option<PathBuf>) -> SolverCache {
+        let entries = path
+            .as_deref()
+            .and_then(load_locked)
+            .unwrap_or_default();
+
+        SolverCache { path, entries }
+    }
+
+    /// Looks up a previously-solved path condition by fingerprint.
+    pub fn get(&self, fingerprint: Fingerprint) -> Option<CachedSatResult> {
+        self.entries.get(&fingerprint).copied()
+    }
+
+    /// Records a solver result and, if backed by a file, persists it immediately. Other
+    /// processes see it only when they next call [`SolverCache::open`]; an already-open cache
+    /// does not re-read the file.
+    pub fn insert(&mut self, fingerprint: Fingerprint, result: CachedSatResult) {
+        self.entries.insert(fingerprint, result);
+
+        if let Some(path) = &self.path {
+            if let Err(e) = save_locked(path, fingerprint, result) {
+                // The cache is an optimisation, never a correctness requirement:
+                // a failed write just means the next process re-solves this entry.
+                eprintln!("solver cache: failed to persist {:?}: {}", path, e);
+            }
+        }
+    }
+}
+
+fn load_locked(path: &Path) -> Option<HashMap<Fingerprint, CachedSatResult>> {
+    let file = OpenOptions::new().read(true).open(path).ok()?;
+    file.lock_shared().ok()?;
+    let result = serde_json::from_reader(BufReader::new(&file)).ok();
+    file.unlock().ok()?;
+    result
+}
+
+/// Persists `fingerprint -> result` into `path`'s entries, re-reading whatever's currently on
+/// disk under the same exclusive lock before writing back. `open()` only loads `entries` once at
+/// process startup, so writing back that stale in-memory snapshot on every `insert` would let
+/// concurrent processes clobber each other's already-persisted entries — the read and the write
+/// have to happen as one locked critical section, not just the write half of it, for the file to
+/// actually end up with the union of what every process inserted.
+fn save_locked(path: &Path, fingerprint: Fingerprint, result: CachedSatResult) -> std::io::Result<()> {
+    let mut file = OpenOptions::new().read(true).write(true).create(true).open(path)?;
+    file.lock_exclusive()?;
+
+    let mut entries: HashMap<Fingerprint, CachedSatResult> =
+        serde_json::from_reader(BufReader::new(&file)).unwrap_or_default();
+    entries.insert(fingerprint, result);
+
+    file.seek(SeekFrom::Start(0))?;
+    file.set_len(0)?;
+    serde_json::to_writer(BufWriter::new(&file), &entries)?;
+    file.unlock()
+}
+
+/// Computes the fingerprint of `expression` by canonicalizing it and hashing the result.
+///
+/// Canonicalization: operands of commutative `BinOp`s (`And`, `Or`, `Equal`, `NotEqual`, `Plus`,
+/// `Multiply`) are sorted by their own fingerprint so operand order never affects the result, and
+/// every symbolic variable / `SymbolicRef` name is replaced by its rank of first appearance in a
+/// left-to-right traversal, so two path conditions that differ only by fresh-variable naming
+/// still collide. Only `BinOp` and `UnOp` nodes are descended into: any other expression kind
+/// is hashed by its `Debug` text as-is, so symbolic names nested inside it are not renamed.
+///
+/// Sorting and renaming happen as two separate passes over the expression, in that order: a
+/// `Shape` pass builds a renaming-independent tree (still carrying each variable's real name)
+/// and sorts commutative operands by hashing it, then a `Canonical` pass walks that already-sorted
+/// tree left-to-right assigning renaming ids. Doing renaming during the same traversal that
+/// decides sort order would make the ids — and so the final hash — depend on the original
+/// textual order of commutative sub-expressions: `x == 1 && y == 2` assigns `x` id 0 before
+/// sorting even looks at `y == 2`, while `y == 2 && x == 1` assigns `y` id 0 instead, so the two
+/// conjuncts would hash differently despite being structurally identical up to reordering.
+pub fn fingerprint(expression: &Rc<Expression>) -> Fingerprint {
+    let shape = shape_of(expression);
+    let mut renaming = HashMap::new();
+    let canonical = canonicalize(&shape, &mut renaming);
+    hash_canonical(&canonical)
+}
+
+/// A renaming-independent shadow of `Expression`, used only to fix commutative operand order
+/// before [`canonicalize`] assigns renaming ids.
+#[derive(Hash)]
+enum Shape {
+    Lit(String),
+    Var(Identifier),
+    BinOp(BinOp, Vec<Shape>),
+    UnOp(String, Box<Shape>),
+    Other(String),
+}
+
+/// A canonical, hashable shadow of `Expression` used only to compute fingerprints.
+#[derive(Hash)]
+enum Canonical {
+    Lit(String),
+    Var(u32),
+    BinOp(BinOp, Vec<Canonical>),
+    UnOp(String, Box<Canonical>),
+    Other(String),
+}
+
+fn shape_of(expression: &Rc<Expression>) -> Shape {
+    match expression.as_ref() {
+        Expression::Lit { lit, .. } => Shape::Lit(format!("{:?}", lit)),
+        Expression::SymbolicVar { var, .. } | Expression::SymbolicRef { var, .. } => Shape::Var(var.clone()),
+        Expression::BinOp {
+            bin_op, lhs, rhs, ..
+        } => {
+            let mut operands = vec![shape_of(lhs), shape_of(rhs)];
+            if is_commutative(*bin_op) {
+                operands.sort_by_key(hash_shape);
+            }
+            Shape::BinOp(*bin_op, operands)
+        }
+        Expression::UnOp { un_op, value, .. } => Shape::UnOp(format!("{:?}", un_op), Box::new(shape_of(value))),
+        other => Shape::Other(format!("{:?}", other)),
+    }
+}
+
+/// Walks `shape` (already in its final, sort-fixed operand order) left-to-right, replacing
+/// each variable with its rank of first appearance.
+fn canonicalize(shape: &Shape, renaming: &mut HashMap<Identifier, u32>) -> Canonical {
+    match shape {
+        Shape::Lit(lit) => Canonical::Lit(lit.clone()),
+        Shape::Var(var) => {
+            let next_id = renaming.len() as u32;
+            let id = *renaming.entry(var.clone()).or_insert(next_id);
+            Canonical::Var(id)
+        }
+        Shape::BinOp(bin_op, operands) => {
+            Canonical::BinOp(*bin_op, operands.iter().map(|operand| canonicalize(operand, renaming)).collect())
+        }
+        Shape::UnOp(un_op, value) => Canonical::UnOp(un_op.clone(), Box::new(canonicalize(value, renaming))),
+        Shape::Other(other) => Canonical::Other(other.clone()),
+    }
+}
+
+fn is_commutative(op: BinOp) -> bool {
+    matches!(
+        op,
+        BinOp::And | BinOp::Or | BinOp::Equal | BinOp::NotEqual | BinOp::Plus | BinOp::Multiply
+    )
+}
+
+/// The sort key for a commutative operand. Canonicalizing `shape` in isolation — a renaming
+/// fresh to this operand alone, not shared with its siblings — assigns variable ids by first
+/// occurrence the same way regardless of what the variable is actually named, so two
+/// alpha-equivalent operands always produce the same key and sort into the same relative
+/// position. Hashing the raw `Shape` instead (which still carries each variable's real name)
+/// would let two structurally-identical operands that differ only by fresh variable naming sort
+/// differently depending on how their names happen to hash, defeating the exact alpha-invariance
+/// [`canonicalize`]'s later, shared-renaming pass is meant to produce.
+fn hash_shape(shape: &Shape) -> Fingerprint {
+    hash_canonical(&canonicalize(shape, &mut HashMap::new()))
+}
+
+fn hash_canonical(canonical: &Canonical) -> Fingerprint {
+    stable_hash(canonical)
+}
+
+fn stable_hash<T: std::hash::Hash>(value: &T) -> Fingerprint {
+    use std::hash::{Hash, Hasher};
+
+    // A single 64-bit hasher only gives us 64 bits of entropy; combine two independently
+    // seeded passes into a 128-bit fingerprint, mirroring rustc's stable-fingerprint approach.
+    let mut lo = std::collections::hash_map::DefaultHasher::new();
+    value.hash(&mut lo);
+    let mut hi = std::collections::hash_map::DefaultHasher::new();
+    0xC0FFEEu64.hash(&mut hi);
+    value.hash(&mut hi);
+
+    ((hi.finish() as u128) << 64) | (lo.finish() as u128)
+}
@@ -0,0 +1,294 @@
+use std::{collections::HashMap, rc::Rc};
+
+use itertools::Itertools;
+
+use crate::{positioned::SourcePos, typeable::Typeable, BinOp, Expression, RuntimeType};
+
+use super::{HeapValue, State};
+
+/// Governs how aggressively [`merge_states`] recombines states that `state_split` fanned out,
+/// trading solver-expression size (bigger `ite` trees) against worklist size (fewer states).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MergePolicy {
+    /// Never merge; preserves the current split-only behaviour.
+    #[default]
+    Disabled,
+    /// Only merge states that have reached a CFG join point (a pc with more than one
+    /// incoming flow edge), where the fan-out from a single branch has necessarily reconverged.
+    JoinPointsOnly,
+    /// Merge any states at the same pc whose top stack frames and heaps differ in at most `n`
+    /// bindings, fields or elements in total, regardless of whether the pc is a join point.
+    BoundedDivergence(usize),
+}
+
+/// Fuses states that have reached the same program point and have structurally compatible
+/// stacks into a single state, replacing divergent variable bindings with `Expression::Conditional`
+/// nodes guarded by each predecessor's path condition, and disjoining the path conditions
+/// themselves. This is the inverse of the fan-out `split_states_with_aliases`/`conditional_state_split`
+/// perform, and directly counters the state-count explosion `SymbolicRef` alias resolution causes.
+pub(super) fn merge_states(states: Vec<State>, policy: MergePolicy, join_points: &[u64]) -> Vec<State> {
+    if policy == MergePolicy::Disabled {
+        return states;
+    }
+
+    let mut by_pc: HashMap<u64, Vec<State>> = HashMap::new();
+    for state in states {
+        let pc = state.threads[&state.active_thread].pc;
+        by_pc.entry(pc).or_default().push(state);
+    }
+
+    by_pc
+        .into_iter()
+        .flat_map(|(pc, group)| merge_group(pc, group, policy, join_points))
+        .collect()
+}
+
+fn merge_group(pc: u64, group: Vec<State>, policy: MergePolicy, join_points: &[u64]) -> Vec<State> {
+    if group.len() <= 1 {
+        return group;
+    }
+
+    if policy == MergePolicy::JoinPointsOnly && !join_points.contains(&pc) {
+        return group;
+    }
+
+    let divergence_limit = match policy {
+        MergePolicy::BoundedDivergence(n) => n,
+        _ => usize::MAX,
+    };
+
+    group
+        .into_iter()
+        .fold(Vec::<State>::new(), |mut merged, candidate| {
+            if let Some(slot) = merged.iter_mut().find(|existing| {
+                divergence(existing, &candidate) <= divergence_limit && heap_compatible(existing, &candidate)
+            }) {
+                let fused = merge_pair(slot.clone(), candidate);
+                *slot = fused;
+            } else {
+                merged.push(candidate);
+            }
+            merged
+        })
+}
+
+/// Program points with more than one incoming flow edge, i.e. where previously-split
+/// branches necessarily reconverge.
+pub(super) fn join_points_of(flows: &HashMap<u64, Vec<u64>>) -> Vec<u64> {
+    let mut incoming_count: HashMap<u64, u32> = HashMap::new();
+    for targets in flows.values() {
+        for target in targets {
+            *incoming_count.entry(*target).or_insert(0) += 1;
+        }
+    }
+
+    incoming_count
+        .into_iter()
+        .filter(|(_, count)| *count > 1)
+        .map(|(pc, _)| pc)
+        .collect()
+}
+
+/// Number of stack bindings and heap fields/elements that differ between `a` and `b`, across
+/// every thread, not just the active one — a merge that only reconciled the active thread could
+/// silently paper over a divergence in some other thread's bindings. A thread that only exists
+/// on one side (spawned on one branch but not the other) counts all of its top-frame bindings as
+/// divergent, mirroring how a heap reference allocated on only one side is treated below. This
+/// still only looks at each thread's top stack frame: `Stack` doesn't expose its full frame list
+/// anywhere in this tree, so a divergence in a caller's frame beneath the top goes undetected.
+fn divergence(a: &State, b: &State) -> usize {
+    let stack_divergence = a
+        .threads
+        .keys()
+        .chain(b.threads.keys())
+        .unique()
+        .map(|tid| match (a.threads.get(tid), b.threads.get(tid)) {
+            (Some(a_thread), Some(b_thread)) => {
+                let a_frame = &a_thread.stack.current_stackframe().params;
+                let b_frame = &b_thread.stack.current_stackframe().params;
+                a_frame.keys().chain(b_frame.keys()).unique().filter(|var| a_frame.get(*var) != b_frame.get(*var)).count()
+            }
+            (Some(thread), None) | (None, Some(thread)) => thread.stack.current_stackframe().params.len(),
+            (None, None) => unreachable!("tid came from one of the two threads maps"),
+        })
+        .sum::<usize>();
+
+    let heap_divergence = a
+        .heap
+        .keys()
+        .chain(b.heap.keys())
+        .unique()
+        .map(|reference| match (a.heap.get(reference), b.heap.get(reference)) {
+            (Some(a_value), Some(b_value)) => heap_value_divergence(a_value, b_value),
+            _ => 1,
+        })
+        .fold(0usize, usize::saturating_add);
+
+    stack_divergence.saturating_add(heap_divergence)
+}
+
+/// Number of fields (for an object) or elements (for an array) that differ between two heap
+/// values known to live under the same reference. Differing shapes (object vs. array, or a
+/// different field/element count) aren't comparable field-by-field, so they're reported as
+/// maximally divergent instead of panicking.
+fn heap_value_divergence(a: &HeapValue, b: &HeapValue) -> usize {
+    match (a, b) {
+        (HeapValue::ObjectValue { fields: a_fields, .. }, HeapValue::ObjectValue { fields: b_fields, .. }) => a_fields
+            .keys()
+            .chain(b_fields.keys())
+            .unique()
+            .filter(|field| a_fields.get(*field) != b_fields.get(*field))
+            .count(),
+        (HeapValue::ArrayValue { elements: a_elems, .. }, HeapValue::ArrayValue { elements: b_elems, .. })
+            if a_elems.len() == b_elems.len() =>
+        {
+            a_elems.iter().zip(b_elems.iter()).filter(|(x, y)| x != y).count()
+        }
+        _ => usize::MAX,
+    }
+}
+
+/// Whether `a` and `b` have a mergeable heap shape: every reference allocated on both sides
+/// must be the same kind of value (object vs. array) with the same fields or element count, so
+/// a per-field/per-element `Expression::Conditional` can actually be constructed. A reference
+/// only allocated on one side is always compatible — `merge_pair` just carries it over as-is.
+fn heap_compatible(a: &State, b: &State) -> bool {
+    a.heap.iter().all(|(reference, a_value)| match b.heap.get(reference) {
+        Some(b_value) => heap_value_divergence(a_value, b_value) != usize::MAX,
+        None => true,
+    })
+}
+
+/// Fuses `a` and `b`'s top stack frame for every thread that exists on both sides (not just
+/// `active_thread`), plus the heap, into `a`, then disjoins the two path conditions. A thread
+/// only spawned on one side is carried over as-is, the same treatment `merge_pair` already gives
+/// a heap reference only allocated on one side. Frames beneath the top of each thread's call
+/// stack aren't merged: `Stack` doesn't expose its full frame list anywhere in this tree, so a
+/// binding divergence in a caller's frame survives the merge unreconciled, same limitation noted
+/// on [`divergence`].
+fn merge_pair(mut a: State, mut b: State) -> State {
+    let guard_a = a.path_condition.clone();
+    let guard_b = b.path_condition.clone();
+
+    let thread_ids = a.threads.keys().chain(b.threads.keys()).copied().unique().collect_vec();
+    for tid in thread_ids {
+        let b_thread = match b.threads.get_mut(&tid) {
+            Some(b_thread) => b_thread,
+            None => continue,
+        };
+        let Some(a_thread) = a.threads.get_mut(&tid) else {
+            a.threads.insert(tid, b_thread.clone());
+            continue;
+        };
+
+        let b_params = b_thread.stack.current_stackframe().params.clone();
+        let a_frame = a_thread.stack.current_stackframe_mut();
+        for (var, b_value) in b_params {
+            let merged_value = match a_frame.params.get(&var) {
+                Some(a_value) if *a_value == b_value => a_value.clone(),
+                Some(a_value) => Rc::new(Expression::Conditional {
+                    guard: guard_a.clone(),
+                    true_: a_value.clone(),
+                    false_: b_value.clone(),
+                    type_: a_value.type_of(),
+                    info: SourcePos::UnknownPosition,
+                }),
+                None => b_value,
+            };
+            a_frame.params.insert(var, merged_value);
+        }
+    }
+
+    for (reference, b_value) in b.heap {
+        let merged_value = match a.heap.get(&reference) {
+            Some(a_value) => merge_heap_value(a_value.clone(), b_value, &guard_a),
+            None => b_value,
+        };
+        a.heap.insert(reference, merged_value);
+    }
+
+    a.path_condition = Rc::new(Expression::BinOp {
+        bin_op: BinOp::Or,
+        lhs: guard_a,
+        rhs: guard_b,
+        type_: RuntimeType::BoolRuntimeType,
+        info: SourcePos::UnknownPosition,
+    });
+
+    a
+}
+
+/// Fuses two heap values known (via [`heap_compatible`]) to share a shape, replacing each
+/// divergent field or element with an `Expression::Conditional` guarded by `guard_a`, the same
+/// technique [`merge_pair`] already uses for stack-frame bindings.
+fn merge_heap_value(a: HeapValue, b: HeapValue, guard_a: &Rc<Expression>) -> HeapValue {
+    let conditional = |a_value: Rc<Expression>, b_value: Rc<Expression>| {
+        if a_value == b_value {
+            return a_value;
+        }
+        Rc::new(Expression::Conditional {
+            guard: guard_a.clone(),
+            true_: a_value.clone(),
+            false_: b_value,
+            type_: a_value.type_of(),
+            info: SourcePos::UnknownPosition,
+        })
+    };
+
+    match (a, b) {
+        (HeapValue::ObjectValue { mut fields, type_ }, HeapValue::ObjectValue { fields: b_fields, .. }) => {
+            for (field, b_value) in b_fields {
+                let merged = match fields.get(&field) {
+                    Some(a_value) => conditional(a_value.clone(), b_value),
+                    None => b_value,
+                };
+                fields.insert(field, merged);
+            }
+            HeapValue::ObjectValue { fields, type_ }
+        }
+        (HeapValue::ArrayValue { elements: a_elems, type_ }, HeapValue::ArrayValue { elements: b_elems, .. }) => {
+            let elements = a_elems.into_iter().zip(b_elems).map(|(a_value, b_value)| conditional(a_value, b_value)).collect();
+            HeapValue::ArrayValue { elements, type_ }
+        }
+        // heap_compatible only lets same-shape pairs reach merge_pair; anything else can't
+        // happen, so fall back to the left-hand value rather than panicking.
+        (a, _) => a,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // `merge_pair`/`divergence`/`heap_value_divergence` all take a `State` (or `HeapValue`,
+    // whose field type is `Rc<Expression>`) and this tree has neither `State`/`Stack`'s field
+    // layout nor the `Lit` enum's variants visible anywhere to construct a fixture from, so
+    // those are left untested here; `join_points_of` needs nothing but plain maps of pcs.
+
+    fn flows(edges: &[(u64, u64)]) -> HashMap<u64, Vec<u64>> {
+        let mut flows: HashMap<u64, Vec<u64>> = HashMap::new();
+        for (from, to) in edges {
+            flows.entry(*from).or_default().push(*to);
+        }
+        flows
+    }
+
+    #[test]
+    fn straight_line_has_no_join_points() {
+        let flows = flows(&[(0, 1), (1, 2), (2, 3)]);
+        assert!(join_points_of(&flows).is_empty());
+    }
+
+    #[test]
+    fn branch_target_with_two_incoming_edges_is_a_join_point() {
+        // 0 -> 1, 0 -> 2, 1 -> 3, 2 -> 3
+        let flows = flows(&[(0, 1), (0, 2), (1, 3), (2, 3)]);
+        assert_eq!(join_points_of(&flows), vec![3]);
+    }
+
+    #[test]
+    fn three_way_merge_is_still_a_single_join_point() {
+        let flows = flows(&[(0, 3), (1, 3), (2, 3)]);
+        assert_eq!(join_points_of(&flows), vec![3]);
+    }
+}